@@ -1,5 +1,13 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use crate::parser::{SubstitutionPolicy, WorkingDir};
+use crate::services::{
+    cascade_files, find_root, resolve_cascade, search_path, Config, Convention, EnvFormat,
+    EnvLoader, ExpandPrecedence, ExportStyle, ExportTarget, ImportFormat, LintFormat, QuoteStyle,
+    Redaction, SubstitutionConfig, DEFAULT_ENV_NAME,
+};
+use crate::utils::error::Result;
+use clap::{Args, Parser, Subcommand};
+use glob::Pattern;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "dotenvx")]
@@ -13,6 +21,10 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub quiet: bool,
 
+    /// Reject invalid variable names and duplicate keys in .env files
+    #[arg(long, global = true, env = "DOTENVX_STRICT")]
+    pub strict: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -44,9 +56,16 @@ pub enum Commands {
         #[arg(short = 'e', long = "exclude-key")]
         exclude_keys: Option<Vec<String>>,
 
+        /// Schema whose @sensitive keys must be encrypted (defaults to .env.schema or .env.example)
+        #[arg(long, value_name = "FILE")]
+        schema: Option<PathBuf>,
+
         /// Output to stdout instead of modifying file
         #[arg(long)]
         stdout: bool,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
     },
 
     /// Decrypt environment variables in .env files
@@ -58,6 +77,9 @@ pub enum Commands {
         /// Path to .env.keys file
         #[arg(short = 'k', long = "env-keys-file")]
         keys_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
     },
 
     /// Set an environment variable (encrypted by default)
@@ -86,13 +108,16 @@ pub enum Commands {
         /// Variable name (if not provided, shows all)
         key: Option<String>,
 
-        /// Path to .env file
-        #[arg(short = 'f', long = "env-file", default_value = ".env")]
-        env_file: PathBuf,
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
 
         /// Path to .env.keys file
         #[arg(short = 'k', long = "env-keys-file")]
         keys_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
     },
 
     /// List all .env files in the directory tree
@@ -102,9 +127,207 @@ pub enum Commands {
         directory: PathBuf,
     },
 
+    /// Show where the value of a variable given to `run` comes from
+    Explain {
+        /// Variable name (if not provided, explains every variable set by a file or inline)
+        key: Option<String>,
+
+        /// Inline environment variables (KEY=value), which override files and the environment
+        #[arg(short = 'e', long = "env")]
+        env: Vec<String>,
+
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Path to .env.keys file
+        #[arg(short = 'k', long = "env-keys-file")]
+        keys_file: Option<PathBuf>,
+
+        /// Override existing environment variables
+        #[arg(short = 'o', long)]
+        overload: bool,
+
+        /// Inherit only basic variables such as PATH, HOME and TERM, plus --pass
+        #[arg(long)]
+        clean: bool,
+
+        /// Variables to inherit in a --clean run or keep despite scrubbing, e.g. AWS_* (repeatable)
+        #[arg(long, value_name = "VAR", value_delimiter = ',')]
+        pass: Vec<String>,
+
+        /// Show values instead of masking them
+        #[arg(long)]
+        reveal: bool,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
+
+        #[command(flatten)]
+        eval: EvalArgs,
+    },
+
+    /// Check that .env files define the variables a schema requires
+    Validate {
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Path to .env.keys file
+        #[arg(short = 'k', long = "env-keys-file")]
+        keys_file: Option<PathBuf>,
+
+        /// Schema file (defaults to .env.schema or .env.example next to the first .env file)
+        #[arg(long, value_name = "FILE")]
+        schema: Option<PathBuf>,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
+
+        #[command(flatten)]
+        eval: EvalArgs,
+    },
+
+    /// Check .env files for mistakes and style problems
+    Lint {
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Rewrite files to fix every problem that can be fixed automatically
+        #[arg(long)]
+        fix: bool,
+
+        /// Set a rule's level, e.g. key-order=error or lowercase-key=off
+        #[arg(
+            long = "rule",
+            value_name = "RULE=LEVEL",
+            value_delimiter = ',',
+            env = "DOTENVX_LINT_RULES"
+        )]
+        rules: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = LintFormat::Human)]
+        format: LintFormat,
+    },
+
+    /// Rewrite .env files in a canonical style
+    Fmt {
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Report files that are not formatted instead of rewriting them
+        #[arg(long)]
+        check: bool,
+
+        /// When to quote values
+        #[arg(long, value_enum, default_value_t = QuoteStyle::Needed, env = "DOTENVX_FMT_QUOTE")]
+        quote: QuoteStyle,
+
+        /// When to use the `export` keyword
+        #[arg(long, value_enum, default_value_t = ExportStyle::Auto, env = "DOTENVX_FMT_EXPORT")]
+        export: ExportStyle,
+
+        /// Sort keys alphabetically within each comment-delimited section
+        #[arg(long, env = "DOTENVX_FMT_SORT")]
+        sort: bool,
+    },
+
+    /// Print loaded environment variables for a shell or tool
+    Printenv {
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Path to .env.keys file
+        #[arg(short = 'k', long = "env-keys-file")]
+        keys_file: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = EnvFormat::Bash)]
+        format: EnvFormat,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
+
+        #[command(flatten)]
+        eval: EvalArgs,
+    },
+
+    /// Print Kubernetes or docker-compose manifests built from .env files
+    Export {
+        /// Path(s) to .env file(s)
+        #[arg(short = 'f', long = "env-file")]
+        env_files: Vec<PathBuf>,
+
+        /// Path to .env.keys file
+        #[arg(short = 'k', long = "env-keys-file")]
+        keys_file: Option<PathBuf>,
+
+        /// Kind of manifest to produce
+        #[arg(long, value_enum)]
+        to: ExportTarget,
+
+        /// Resource name, or service name for compose
+        #[arg(long, default_value = "app")]
+        name: String,
+
+        /// Kubernetes namespace
+        #[arg(long)]
+        namespace: Option<String>,
+
+        /// Put keys matching these patterns in a Secret and the rest in a ConfigMap
+        #[arg(long = "secret-keys", value_name = "PATTERN", value_delimiter = ',')]
+        secret_keys: Vec<String>,
+
+        /// Keep values encrypted and print an init container that decrypts them
+        #[arg(long)]
+        sealed: bool,
+
+        #[command(flatten)]
+        eval: EvalArgs,
+    },
+
+    /// Import variables from JSON, YAML, TOML, shell, docker or Secret files
+    Import {
+        /// File to import
+        #[arg(required_unless_present = "from_env")]
+        file: Option<PathBuf>,
+
+        /// Format of the file, inferred from its extension by default
+        #[arg(long, value_enum)]
+        from: Option<ImportFormat>,
+
+        /// Also import process environment variables starting with this prefix
+        #[arg(long = "from-env", value_name = "PREFIX")]
+        from_env: Option<String>,
+
+        /// Path to .env file
+        #[arg(short = 'f', long = "env-file", default_value = ".env")]
+        env_file: PathBuf,
+
+        /// Path to .env.keys file
+        #[arg(short = 'k', long = "env-keys-file")]
+        keys_file: Option<PathBuf>,
+
+        /// Joins the keys of nested values
+        #[arg(long, default_value = "__")]
+        separator: String,
+
+        /// Replace the variables in the .env file instead of merging
+        #[arg(long)]
+        replace: bool,
+
+        /// Store as plain text (don't encrypt)
+        #[arg(short = 'p', long)]
+        plain: bool,
+    },
+
     /// Run a command with environment variables loaded
     Run {
-        /// Inline environment variables (KEY=value)
+        /// Inline environment variables (KEY=value), which override files and the environment
         #[arg(short = 'e', long = "env")]
         env: Vec<String>,
 
@@ -120,8 +343,264 @@ pub enum Commands {
         #[arg(short = 'o', long)]
         overload: bool,
 
+        /// Seconds between forwarding a terminating signal and killing the command (0 waits indefinitely)
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 10,
+            env = "DOTENVX_GRACE_PERIOD"
+        )]
+        grace_period: u64,
+
+        /// Replace dotenvx with the command (the default on Unix when nothing needs dotenvx to keep running)
+        #[arg(long, overrides_with = "no_exec")]
+        exec: bool,
+
+        /// Keep dotenvx running as the command's parent
+        #[arg(long = "no-exec", overrides_with = "exec")]
+        no_exec: bool,
+
+        /// Restart the command when the .env files or keys change
+        #[arg(short = 'w', long)]
+        watch: bool,
+
+        /// Replace decrypted values in the command's output with [REDACTED:KEY] (=encoded also catches base64 and URL-encoded forms)
+        #[arg(
+            long,
+            value_enum,
+            value_name = "FORMS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "plain"
+        )]
+        redact: Option<Redaction>,
+
+        /// Inherit only basic variables such as PATH, HOME and TERM, plus --pass
+        #[arg(long)]
+        clean: bool,
+
+        /// Variables to inherit in a --clean run or keep despite scrubbing, e.g. AWS_* (repeatable)
+        #[arg(long, value_name = "VAR", value_delimiter = ',')]
+        pass: Vec<String>,
+
+        /// Variables to hand over as 0600 files, setting KEY_FILE to the path instead of KEY (comma-separated)
+        #[arg(long = "as-files", value_name = "KEY", value_delimiter = ',')]
+        as_files: Vec<String>,
+
+        /// Like --as-files, for variables matching a glob pattern such as '*_SECRET' (repeatable)
+        #[arg(long = "as-files-pattern", value_name = "PATTERN")]
+        as_files_pattern: Vec<Pattern>,
+
+        /// Print where each variable came from before starting the command
+        #[arg(long)]
+        explain: bool,
+
+        /// Show values in the --explain report instead of masking them
+        #[arg(long, requires = "explain")]
+        reveal: bool,
+
+        /// Check the environment against the schema before starting the command
+        #[arg(long)]
+        validate: bool,
+
+        /// Schema file to validate against (defaults to .env.schema or .env.example)
+        #[arg(long, value_name = "FILE", requires = "validate")]
+        schema: Option<PathBuf>,
+
+        #[command(flatten)]
+        cascade: CascadeArgs,
+
+        #[command(flatten)]
+        eval: EvalArgs,
+
         /// Command to run
         #[arg(last = true, required = true)]
         command: Vec<String>,
     },
 }
+
+/// Options selecting per-environment .env files when no `-f` is given
+#[derive(Args, Debug, Clone)]
+pub struct CascadeArgs {
+    /// Load the .env file cascade of this framework's convention
+    #[arg(long, value_enum)]
+    pub convention: Option<Convention>,
+
+    /// Environment whose .env files to load, such as production
+    #[arg(long = "env-name", value_name = "NAME", env = "DOTENV_ENV")]
+    pub env_name: Option<String>,
+
+    /// Also load .env files and search .env.keys in parent directories, up to the repository root
+    #[arg(long, env = "DOTENVX_DISCOVER")]
+    pub discover: bool,
+
+    /// Directory to stop discovery at instead of the repository root (implies --discover)
+    #[arg(long, value_name = "DIR", env = "DOTENVX_ROOT")]
+    pub root: Option<PathBuf>,
+}
+
+impl CascadeArgs {
+    /// Resolve the files to load
+    ///
+    /// Explicit `-f` files are used as given. Otherwise, a convention or
+    /// environment name selects a cascade in the current directory, and
+    /// without either only `.env` is loaded. With discovery, the same files
+    /// are loaded from every directory between the root and the current
+    /// one, outermost first, so the nearest files win.
+    pub fn files(&self, env_files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        if !env_files.is_empty() {
+            return Ok(env_files.to_vec());
+        }
+
+        let files: Vec<PathBuf> = self
+            .candidates(env_files)?
+            .into_iter()
+            .filter(|f| f.exists())
+            .collect();
+        if files.is_empty() {
+            // Report the nearest .env as missing
+            return Ok(vec![PathBuf::from(".env")]);
+        }
+        Ok(files)
+    }
+
+    /// Every file [`CascadeArgs::files`] would load if it existed, in load order
+    ///
+    /// `run --watch` watches these, so creating one restarts the command.
+    pub fn candidates(&self, env_files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        if !env_files.is_empty() {
+            return Ok(env_files.to_vec());
+        }
+
+        let root = self.root()?;
+        let mut files = Vec::new();
+        for dir in search_path(Path::new(""), root.as_deref()) {
+            files.extend(self.candidates_in(&dir)?);
+        }
+        Ok(files)
+    }
+
+    /// Resolve the files to change in place, such as by `encrypt`
+    ///
+    /// Like [`CascadeArgs::files`], but never from parent directories, which
+    /// discovery only searches for keys.
+    pub fn local_files(&self, env_files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        if !env_files.is_empty() {
+            return Ok(env_files.to_vec());
+        }
+        if self.convention.is_none() && self.env_name.is_none() {
+            return Ok(vec![PathBuf::from(".env")]);
+        }
+
+        resolve_cascade(
+            self.convention.unwrap_or_default(),
+            self.env_name.as_deref().unwrap_or(DEFAULT_ENV_NAME),
+            Path::new(""),
+        )
+    }
+
+    /// The directory discovery stops at, or `None` when it is off or there
+    /// is no repository root above the current directory
+    pub fn root(&self) -> Result<Option<PathBuf>> {
+        if !self.discover && self.root.is_none() {
+            return Ok(None);
+        }
+        find_root(Path::new(""), self.root.as_deref())
+    }
+
+    /// The files that may be loaded from one directory
+    fn candidates_in(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        if self.convention.is_none() && self.env_name.is_none() {
+            return Ok(vec![dir.join(".env")]);
+        }
+
+        cascade_files(
+            self.convention.unwrap_or_default(),
+            self.env_name.as_deref().unwrap_or(DEFAULT_ENV_NAME),
+            dir,
+        )
+    }
+}
+
+/// Options controlling how values are evaluated when loading .env files
+///
+/// Each option can also be set through the `DOTENVX_*` environment variable
+/// shown in `--help`, or in the `[substitution]` table of a `--config` file.
+/// Flags and environment variables take precedence over the config file.
+#[derive(Args, Debug, Clone)]
+pub struct EvalArgs {
+    /// Whether .env files or the process environment win when expanding ${VAR}
+    #[arg(long, value_enum, default_value_t = ExpandPrecedence::File, env = "DOTENVX_EXPAND_PRECEDENCE")]
+    pub expand_precedence: ExpandPrecedence,
+
+    /// TOML file with a [substitution] table of defaults for the options below
+    #[arg(long, value_name = "FILE", env = "DOTENVX_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Never execute $(...) or backtick command substitutions
+    #[arg(long, env = "DOTENVX_NO_SUBSTITUTION")]
+    pub no_substitution: bool,
+
+    /// Executables that command substitutions may invoke (comma-separated)
+    #[arg(
+        long = "substitution-allow",
+        value_name = "EXECUTABLE",
+        value_delimiter = ',',
+        env = "DOTENVX_SUBSTITUTION_ALLOW"
+    )]
+    pub substitution_allow: Option<Vec<String>>,
+
+    /// Seconds before a substitution command is killed (default 30; 0 waits indefinitely)
+    #[arg(long, value_name = "SECONDS", env = "DOTENVX_SUBSTITUTION_TIMEOUT")]
+    pub substitution_timeout: Option<u64>,
+
+    /// Maximum bytes a substitution command may output (default 1 MiB; 0 is unlimited)
+    #[arg(long, value_name = "BYTES", env = "DOTENVX_SUBSTITUTION_MAX_OUTPUT")]
+    pub substitution_max_output: Option<usize>,
+
+    /// Directory that substitution commands run in (default cwd)
+    #[arg(long, value_enum, env = "DOTENVX_SUBSTITUTION_CWD")]
+    pub substitution_cwd: Option<WorkingDir>,
+
+    /// Pass the variables loaded so far to substitution commands
+    #[arg(long, env = "DOTENVX_SUBSTITUTION_PASS_ENV")]
+    pub substitution_pass_env: bool,
+}
+
+impl EvalArgs {
+    /// Build the command substitution policy from these options, falling
+    /// back to the config file and then the defaults
+    pub fn substitution_policy(&self) -> Result<SubstitutionPolicy> {
+        let flags = SubstitutionConfig {
+            enabled: self.no_substitution.then_some(false),
+            allow: self.substitution_allow.clone(),
+            timeout: self.substitution_timeout,
+            max_output: self.substitution_max_output,
+            cwd: self.substitution_cwd,
+            pass_env: self.substitution_pass_env.then_some(true),
+        };
+        let file = match &self.config {
+            Some(path) => Config::load(path)?.substitution,
+            None => SubstitutionConfig::default(),
+        };
+        Ok(flags.or(file).policy())
+    }
+
+    /// Build a loader configured with these options
+    pub fn loader<'a>(&self, keys_file: Option<&'a Path>) -> Result<EnvLoader<'a>> {
+        Ok(EnvLoader::new()
+            .keys_file(keys_file)
+            .precedence(self.expand_precedence)
+            .substitution(self.substitution_policy()?))
+    }
+
+    /// Build a loader for commands that only inspect files, which never run
+    /// command substitutions, since a file may come from someone else
+    pub fn inspect_loader<'a>(&self, keys_file: Option<&'a Path>) -> Result<EnvLoader<'a>> {
+        let policy = self.substitution_policy()?;
+        Ok(self.loader(keys_file)?.substitution(SubstitutionPolicy {
+            enabled: false,
+            ..policy
+        }))
+    }
+}
@@ -1,16 +1,19 @@
+use crate::cli::args::CascadeArgs;
 use crate::services::decrypt_file;
 use crate::utils::Result;
 use std::path::{Path, PathBuf};
 
-pub fn decrypt_command(env_files: &[PathBuf], keys_file: Option<&Path>) -> Result<()> {
-    let files = if env_files.is_empty() {
-        vec![PathBuf::from(".env")]
-    } else {
-        env_files.to_vec()
-    };
+pub fn decrypt_command(
+    env_files: &[PathBuf],
+    cascade: &CascadeArgs,
+    keys_file: Option<&Path>,
+    strict: bool,
+) -> Result<()> {
+    let files = cascade.local_files(env_files)?;
+    let root = cascade.root()?;
 
     for env_file in files {
-        decrypt_file(&env_file, keys_file)?;
+        decrypt_file(&env_file, keys_file, root.as_deref(), strict)?;
     }
 
     Ok(())
@@ -1,22 +1,36 @@
-use crate::services::encrypt_file;
+use crate::cli::args::CascadeArgs;
+use crate::services::{encrypt_file, Schema};
 use crate::utils::Result;
 use std::path::{Path, PathBuf};
 
+#[allow(clippy::too_many_arguments)]
 pub fn encrypt_command(
     env_files: &[PathBuf],
+    cascade: &CascadeArgs,
     keys_file: Option<&Path>,
     keys: Option<&[String]>,
     exclude_keys: Option<&[String]>,
+    schema: Option<&Path>,
     _stdout: bool,
+    strict: bool,
 ) -> Result<()> {
-    let files = if env_files.is_empty() {
-        vec![PathBuf::from(".env")]
-    } else {
-        env_files.to_vec()
-    };
+    let files = cascade.local_files(env_files)?;
+    let root = cascade.root()?;
 
     for env_file in files {
-        encrypt_file(&env_file, keys_file, keys, exclude_keys)?;
+        // Keys the schema marks @sensitive are always encrypted
+        let sensitive = Schema::discover(schema, Some(&env_file))?
+            .map(|schema| schema.sensitive_keys())
+            .unwrap_or_default();
+        encrypt_file(
+            &env_file,
+            keys_file,
+            root.as_deref(),
+            keys,
+            exclude_keys,
+            &sensitive,
+            strict,
+        )?;
     }
 
     Ok(())
@@ -0,0 +1,56 @@
+use crate::cli::args::{CascadeArgs, EvalArgs};
+use crate::services::{explain, prepare_env, RunOptions};
+use crate::utils::Result;
+use std::path::{Path, PathBuf};
+
+/// Print where a variable, or every variable, in `run`'s environment came from
+///
+/// Command substitutions are not run: their values are reported as the
+/// command text, marked "command not run".
+///
+/// # Arguments
+///
+/// * `key` - Variable to explain, or `None` for every variable set by a file or inline
+/// * `inline` - Inline `KEY=value` assignments
+/// * `env_files` - Paths to .env files to load
+/// * `cascade` - Convention and environment used when no files are given
+/// * `keys_file` - Optional path to .env.keys file
+/// * `options` - Environment precedence and scrubbing, as for `run`
+/// * `strict` - Reject invalid variable names and duplicate keys
+/// * `eval` - Options controlling expansion and command substitution
+///
+/// # Returns
+///
+/// Result indicating success or failure
+#[allow(clippy::too_many_arguments)]
+pub fn explain_command(
+    key: Option<&str>,
+    inline: &[String],
+    env_files: &[PathBuf],
+    cascade: &CascadeArgs,
+    keys_file: Option<&Path>,
+    options: &RunOptions,
+    strict: bool,
+    eval: &EvalArgs,
+) -> Result<()> {
+    let files = cascade.files(env_files)?;
+    let root = cascade.root()?;
+    // Missing files are skipped, as `run` does
+    let files: Vec<&Path> = files
+        .iter()
+        .filter(|f| f.exists())
+        .map(|p| p.as_path())
+        .collect();
+
+    let loader = eval
+        .inspect_loader(keys_file)?
+        .root(root.as_deref())
+        .process_env(options.inherited_env())
+        .strict(strict);
+    let inline = loader.parse_inline(inline)?;
+    let loader = loader.inline(inline);
+
+    let env = prepare_env(&files, &loader, options)?;
+    print!("{}", explain(&env, key, options.reveal)?);
+    Ok(())
+}
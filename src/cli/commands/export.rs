@@ -0,0 +1,71 @@
+use crate::cli::args::EvalArgs;
+use crate::services::export::{render_manifest, render_sealed, ExportOptions};
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use std::path::{Path, PathBuf};
+
+/// Print deployment manifests built from .env files
+///
+/// Values that are command substitutions are rejected rather than run.
+///
+/// # Arguments
+///
+/// * `env_files` - Paths to .env files to load
+/// * `keys_file` - Optional path to .env.keys file
+/// * `options` - Target, naming and key routing
+/// * `sealed` - Keep the ciphertext and decrypt in an init container instead
+/// * `strict` - Reject invalid variable names and duplicate keys
+/// * `eval` - Options controlling expansion and command substitution
+///
+/// # Returns
+///
+/// Result indicating success or failure
+pub fn export_command(
+    env_files: &[PathBuf],
+    keys_file: Option<&Path>,
+    options: &ExportOptions,
+    sealed: bool,
+    strict: bool,
+    eval: &EvalArgs,
+) -> Result<()> {
+    let files = if env_files.is_empty() {
+        vec![PathBuf::from(".env")]
+    } else {
+        env_files.to_vec()
+    };
+
+    let manifest = if sealed {
+        let contents = files
+            .iter()
+            .map(|path| {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .ok_or_else(|| {
+                        DotenvxError::Other(format!("invalid env file path: {}", path.display()))
+                    })?;
+                Ok((name, read_file(path)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        render_sealed(&contents, options)?
+    } else {
+        let files: Vec<&Path> = files.iter().map(|p| p.as_path()).collect();
+        // The manifest is built on this machine, so the files' commands are never run here
+        let env = eval
+            .inspect_loader(keys_file)?
+            .strict(strict)
+            .load_detailed(&files)?;
+        let mut unevaluated: Vec<String> = env.unevaluated().into_iter().collect();
+        if !unevaluated.is_empty() {
+            unevaluated.sort();
+            return Err(DotenvxError::Other(format!(
+                "cannot export {}: command substitutions ($(...) or backticks) are not run by export; replace them with their values",
+                unevaluated.join(", ")
+            )));
+        }
+        render_manifest(&env.variables, options)?
+    };
+
+    print!("{}", manifest);
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use crate::services::{format_file, FormatOptions};
+use crate::utils::error::DotenvxError;
+use crate::utils::Result;
+use std::path::PathBuf;
+
+pub fn fmt_command(
+    env_files: &[PathBuf],
+    check: bool,
+    options: &FormatOptions,
+    strict: bool,
+) -> Result<()> {
+    let files = if env_files.is_empty() {
+        vec![PathBuf::from(".env")]
+    } else {
+        env_files.to_vec()
+    };
+
+    let mut unformatted = 0;
+    for env_file in &files {
+        if format_file(env_file, options, check, strict)? && check {
+            println!("Would reformat {}", env_file.display());
+            unformatted += 1;
+        }
+    }
+
+    if unformatted > 0 {
+        return Err(DotenvxError::Unformatted { files: unformatted });
+    }
+
+    Ok(())
+}
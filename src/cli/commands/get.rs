@@ -1,32 +1,31 @@
+use crate::cli::args::CascadeArgs;
 use crate::crypto::decrypt;
-use crate::parser::DotenvParser;
+use crate::parser::{log_warnings, DotenvParser};
+use crate::services::find_private_key;
 use crate::utils::error::Result;
 use crate::utils::fs::read_file;
-use std::path::Path;
-
-pub fn get_command(key: Option<&str>, env_file: &Path, keys_file: Option<&Path>) -> Result<()> {
-    let content = read_file(env_file)?;
-    let mut parser = DotenvParser::new();
-    parser.parse(&content)?;
-
-    let private_key = find_private_key(env_file, keys_file);
-
-    if let Some(key_name) = key {
-        // Get specific key
-        if let Some(value) = parser.variables().get(key_name) {
-            let final_value =
-                if let (true, Ok(key)) = (value.starts_with("encrypted:"), &private_key) {
-                    decrypt(value, key).unwrap_or_else(|_| value.clone())
-                } else {
-                    value.clone()
-                };
-            println!("{}={}", key_name, final_value);
-        } else {
-            eprintln!("Key '{}' not found", key_name);
-            std::process::exit(1);
-        }
-    } else {
-        // Get all keys
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub fn get_command(
+    key: Option<&str>,
+    env_files: &[PathBuf],
+    cascade: &CascadeArgs,
+    keys_file: Option<&Path>,
+    strict: bool,
+) -> Result<()> {
+    // Later files override earlier ones; each is decrypted with its own key
+    let mut variables = HashMap::new();
+    let root = cascade.root()?;
+    for env_file in cascade.files(env_files)? {
+        let content = read_file(&env_file)?;
+        let mut parser = DotenvParser::with_path(&env_file).strict(strict);
+        parser.parse(&content)?;
+        log_warnings(parser.diagnostics());
+
+        let private_key =
+            find_private_key(&env_file, keys_file, "DOTENV_PRIVATE_KEY", root.as_deref())
+                .map(|(key, _)| key);
         for (k, v) in parser.variables() {
             if k == "DOTENV_PUBLIC_KEY" {
                 continue;
@@ -37,50 +36,24 @@ pub fn get_command(key: Option<&str>, env_file: &Path, keys_file: Option<&Path>)
             } else {
                 v.clone()
             };
-            println!("{}={}", k, final_value);
+            variables.insert(k.clone(), final_value);
         }
     }
 
-    Ok(())
-}
-
-fn find_private_key(env_file: &Path, keys_file: Option<&Path>) -> Result<String> {
-    use crate::utils::error::DotenvxError;
-
-    if let Some(keys_path) = keys_file {
-        if keys_path.exists() {
-            let content = read_file(keys_path)?;
-            if let Some(key) = extract_key(&content) {
-                return Ok(key);
-            }
+    if let Some(key_name) = key {
+        // Get specific key
+        if let Some(value) = variables.get(key_name) {
+            println!("{}={}", key_name, value);
+        } else {
+            eprintln!("Key '{}' not found", key_name);
+            std::process::exit(1);
         }
-    }
-
-    if let Some(parent) = env_file.parent() {
-        let default_keys = parent.join(".env.keys");
-        if default_keys.exists() {
-            let content = read_file(&default_keys)?;
-            if let Some(key) = extract_key(&content) {
-                return Ok(key);
-            }
+    } else {
+        // Get all keys
+        for (k, v) in &variables {
+            println!("{}={}", k, v);
         }
     }
 
-    if let Ok(key) = std::env::var("DOTENV_PRIVATE_KEY") {
-        return Ok(key);
-    }
-
-    Err(DotenvxError::MissingPrivateKey {
-        key_name: "DOTENV_PRIVATE_KEY".to_string(),
-    })
-}
-
-fn extract_key(content: &str) -> Option<String> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if let Some(value) = trimmed.strip_prefix("DOTENV_PRIVATE_KEY=") {
-            return Some(value.trim_matches('"').to_string());
-        }
-    }
-    None
+    Ok(())
 }
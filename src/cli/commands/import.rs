@@ -0,0 +1,66 @@
+use crate::services::import::{from_env, parse_import, ImportFormat};
+use crate::services::set_values;
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use std::path::Path;
+
+/// Import variables from another format into a .env file
+///
+/// # Arguments
+///
+/// * `file` - Optional file to import
+/// * `from` - Format of the file, inferred from its extension if omitted
+/// * `env_prefix` - Optional prefix of process environment variables to import
+/// * `env_file` - Path to the .env file to write
+/// * `keys_file` - Optional path to .env.keys file
+/// * `separator` - Joins the keys of nested values
+/// * `replace` - Replace the existing variables instead of merging
+/// * `plain` - Store values as plain text
+/// * `strict` - Reject invalid variable names and duplicate keys
+///
+/// # Returns
+///
+/// Result indicating success or failure
+#[allow(clippy::too_many_arguments)]
+pub fn import_command(
+    file: Option<&Path>,
+    from: Option<ImportFormat>,
+    env_prefix: Option<&str>,
+    env_file: &Path,
+    keys_file: Option<&Path>,
+    separator: &str,
+    replace: bool,
+    plain: bool,
+    strict: bool,
+) -> Result<()> {
+    let mut values = Vec::new();
+
+    if let Some(file) = file {
+        let format = from
+            .or_else(|| ImportFormat::from_path(file))
+            .ok_or_else(|| {
+                DotenvxError::Other(format!(
+                    "cannot infer the format of {}; pass --from",
+                    file.display()
+                ))
+            })?;
+        values.extend(parse_import(&read_file(file)?, format, separator)?);
+    }
+    if let Some(prefix) = env_prefix {
+        values.extend(from_env(prefix));
+    }
+
+    if values.is_empty() {
+        println!("no variables to import");
+        return Ok(());
+    }
+
+    set_values(env_file, keys_file, &values, plain, true, replace, strict)?;
+    println!(
+        "✔ imported {} variable(s) into {}",
+        values.len(),
+        env_file.display()
+    );
+
+    Ok(())
+}
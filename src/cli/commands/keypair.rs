@@ -1,11 +1,18 @@
 use crate::crypto::Keypair;
+use crate::parser::format_entry;
 use crate::utils::Result;
 
 pub fn keypair_command(_format: &str) -> Result<()> {
     let keypair = Keypair::generate();
 
-    println!("DOTENV_PUBLIC_KEY=\"{}\"", keypair.public_key());
-    println!("DOTENV_PRIVATE_KEY=\"{}\"", keypair.private_key());
+    println!(
+        "{}",
+        format_entry("DOTENV_PUBLIC_KEY", &keypair.public_key())
+    );
+    println!(
+        "{}",
+        format_entry("DOTENV_PRIVATE_KEY", &keypair.private_key())
+    );
 
     Ok(())
 }
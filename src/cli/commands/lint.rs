@@ -0,0 +1,47 @@
+use crate::services::lint::render_json;
+use crate::services::{lint_file, LintConfig, LintFormat};
+use crate::utils::error::DotenvxError;
+use crate::utils::Result;
+use std::path::PathBuf;
+
+pub fn lint_command(
+    env_files: &[PathBuf],
+    fix: bool,
+    rules: &[String],
+    format: LintFormat,
+) -> Result<()> {
+    let files = if env_files.is_empty() {
+        vec![PathBuf::from(".env")]
+    } else {
+        env_files.to_vec()
+    };
+
+    let mut config = LintConfig::default();
+    for rule in rules {
+        config.set(rule)?;
+    }
+
+    let mut diagnostics = Vec::new();
+    for env_file in &files {
+        diagnostics.extend(lint_file(env_file, &config, fix)?);
+    }
+
+    match format {
+        LintFormat::Json => println!("{}", render_json(&diagnostics)),
+        LintFormat::Human => {
+            for diagnostic in &diagnostics {
+                println!("{}\n", diagnostic);
+            }
+            if diagnostics.is_empty() {
+                println!("✔ no problems found in {} file(s)", files.len());
+            }
+        }
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    if errors > 0 {
+        return Err(DotenvxError::LintFailed { errors });
+    }
+
+    Ok(())
+}
@@ -1,15 +1,29 @@
 pub mod decrypt;
 pub mod encrypt;
+pub mod explain;
+pub mod export;
+pub mod fmt;
 pub mod get;
+pub mod import;
 pub mod keypair;
+pub mod lint;
 pub mod ls;
+pub mod printenv;
 pub mod run;
 pub mod set;
+pub mod validate;
 
 pub use decrypt::decrypt_command;
 pub use encrypt::encrypt_command;
+pub use explain::explain_command;
+pub use export::export_command;
+pub use fmt::fmt_command;
 pub use get::get_command;
+pub use import::import_command;
 pub use keypair::keypair_command;
+pub use lint::lint_command;
 pub use ls::ls_command;
+pub use printenv::printenv_command;
 pub use run::run_command;
 pub use set::set_command;
+pub use validate::validate_command;
@@ -1,11 +1,18 @@
-use crate::services::run_command as run_service;
+use crate::cli::args::{CascadeArgs, EvalArgs};
+use crate::services::{run_command as run_service, RunOptions, Schema};
 use crate::utils::Result;
 use std::path::{Path, PathBuf};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run_command(
+    inline: &[String],
     env_files: &[PathBuf],
+    cascade: &CascadeArgs,
     keys_file: Option<&Path>,
-    overload: bool,
+    validate: Option<Option<&Path>>,
+    options: RunOptions,
+    strict: bool,
+    eval: &EvalArgs,
     command: &[String],
 ) -> Result<i32> {
     if command.is_empty() {
@@ -13,21 +20,30 @@ pub async fn run_command(
         std::process::exit(1);
     }
 
-    let files = if env_files.is_empty() {
-        vec![PathBuf::from(".env")]
-    } else {
-        env_files.to_vec()
-    };
+    let files = cascade.files(env_files)?;
+    let root = cascade.root()?;
+    let mut options = options;
+    if let Some(schema) = validate {
+        options.schema = Some(Schema::require(
+            schema,
+            files.first().map(PathBuf::as_path),
+        )?);
+    }
 
-    // Filter to only existing files
-    let existing_files: Vec<&Path> = files
-        .iter()
-        .filter(|f| f.exists())
-        .map(|p| p.as_path())
-        .collect();
+    // Missing files are skipped when loading, but watched in case they are created
+    let candidates = cascade.candidates(env_files)?;
+    let candidates: Vec<&Path> = candidates.iter().map(|p| p.as_path()).collect();
 
     let cmd = &command[0];
     let args: Vec<String> = command[1..].to_vec();
 
-    run_service(&existing_files, keys_file, cmd, &args, overload).await
+    // References resolve against what the command would inherit, not the whole environment
+    let loader = eval
+        .loader(keys_file)?
+        .root(root.as_deref())
+        .process_env(options.inherited_env())
+        .strict(strict);
+    let inline = loader.parse_inline(inline)?;
+    let loader = loader.inline(inline);
+    run_service(&candidates, &loader, cmd, &args, &options).await
 }
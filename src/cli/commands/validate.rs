@@ -0,0 +1,50 @@
+use crate::cli::args::{CascadeArgs, EvalArgs};
+use crate::services::Schema;
+use crate::utils::error::Result;
+use std::path::{Path, PathBuf};
+
+/// Check the merged, decrypted .env files against a schema
+///
+/// Command substitutions are not run, so their keys are only checked for
+/// being set.
+///
+/// # Arguments
+///
+/// * `env_files` - Paths to .env files to load
+/// * `cascade` - Convention and environment used when no files are given
+/// * `keys_file` - Optional path to .env.keys file
+/// * `schema` - Schema file, or `None` to look for one next to the first file
+/// * `strict` - Reject invalid variable names and duplicate keys
+/// * `eval` - Options controlling expansion and command substitution
+///
+/// # Returns
+///
+/// An error listing every violation if the files do not match the schema
+pub fn validate_command(
+    env_files: &[PathBuf],
+    cascade: &CascadeArgs,
+    keys_file: Option<&Path>,
+    schema: Option<&Path>,
+    strict: bool,
+    eval: &EvalArgs,
+) -> Result<()> {
+    let files = cascade.files(env_files)?;
+    let root = cascade.root()?;
+    let schema = Schema::require(schema, files.first().map(PathBuf::as_path))?;
+    let files: Vec<&Path> = files.iter().map(|p| p.as_path()).collect();
+
+    // Checking a schema never runs the files' commands
+    let env = eval
+        .inspect_loader(keys_file)?
+        .root(root.as_deref())
+        .strict(strict)
+        .load_detailed(&files)?;
+    schema.check(&env.variables, &env.unevaluated())?;
+
+    println!(
+        "✔ {} variable(s) checked in {} file(s)",
+        schema.fields().len(),
+        files.len()
+    );
+    Ok(())
+}
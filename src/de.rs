@@ -0,0 +1,625 @@
+//! Deserialize .env files into typed configuration structs
+//!
+//! ```rust,no_run
+//! use serde::Deserialize;
+//! use std::time::Duration;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     port: u16,
+//!     debug: bool,
+//!     timeout: Duration,
+//!     allowed_hosts: Vec<String>,
+//!     db: Database,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Database {
+//!     host: String,
+//!     pool_size: Option<u32>,
+//! }
+//!
+//! let config: Config = dotenvx::from_str(
+//!     "PORT=8080\nDEBUG=true\nTIMEOUT=1m30s\nALLOWED_HOSTS=a.com,b.com\nDB__HOST=localhost\n",
+//! )
+//! .unwrap();
+//! ```
+//!
+//! Field names are matched against upper-cased variable names, and nested
+//! structs are read from variables prefixed with the field name and `__`.
+
+use crate::crypto::decrypt;
+use crate::parser::{DotenvParser, SubstitutionPolicy};
+use crate::services::EnvLoader;
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Separator between a nested struct's prefix and its field names
+pub const NESTING_SEPARATOR: &str = "__";
+
+impl de::Error for DotenvxError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        DotenvxError::Deserialize(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        DotenvxError::MissingField(field.to_string())
+    }
+}
+
+/// Deserialize a value of type `T` from .env content
+///
+/// Variables are expanded within the content; `encrypted:` values cannot be
+/// read without a key, see [`from_str_with_key`].
+///
+/// # Arguments
+///
+/// * `content` - The content of a .env file
+///
+/// # Returns
+///
+/// The deserialized value
+pub fn from_str<T: DeserializeOwned>(content: &str) -> Result<T> {
+    let mut parser = DotenvParser::new();
+    parser.parse(content)?;
+    parser.expand()?;
+    from_map(parser.variables())
+}
+
+/// Deserialize a value of type `T` from .env content, decrypting encrypted values
+///
+/// # Arguments
+///
+/// * `content` - The content of a .env file
+/// * `private_key` - Hex private key used to decrypt `encrypted:` values
+///
+/// # Returns
+///
+/// The deserialized value
+pub fn from_str_with_key<T: DeserializeOwned>(content: &str, private_key: &str) -> Result<T> {
+    let mut parser = DotenvParser::new();
+    parser.parse(content)?;
+    for index in 0..parser.entries().len() {
+        let entry = &parser.entries()[index];
+        if entry.encrypted {
+            let decrypted = decrypt(&entry.value, private_key)?;
+            parser.set_value(index, decrypted);
+        }
+    }
+    parser.expand()?;
+    from_map(parser.variables())
+}
+
+/// Load a .env file and deserialize a value of type `T` from it
+///
+/// The file is loaded like `dotenvx run` loads it, except that command
+/// substitutions are never executed: encrypted values are decrypted with the
+/// key from the neighbouring `.env.keys` file or `DOTENV_PRIVATE_KEY`, and
+/// references may use the process environment.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .env file
+///
+/// # Returns
+///
+/// The deserialized value
+pub fn from_path<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let policy = SubstitutionPolicy {
+        enabled: false,
+        ..SubstitutionPolicy::default()
+    };
+    let variables = EnvLoader::new().substitution(policy).load(&[path])?;
+    from_map(&variables)
+}
+
+/// Load a .env file and deserialize a value of type `T` from it, decrypting with `private_key`
+///
+/// # Arguments
+///
+/// * `path` - Path to the .env file
+/// * `private_key` - Hex private key used to decrypt `encrypted:` values
+///
+/// # Returns
+///
+/// The deserialized value
+pub fn from_path_with_key<T: DeserializeOwned>(path: &Path, private_key: &str) -> Result<T> {
+    from_str_with_key(&read_file(path)?, private_key)
+}
+
+/// Deserialize a value of type `T` from already loaded variables
+pub fn from_map<T: DeserializeOwned>(variables: &HashMap<String, String>) -> Result<T> {
+    T::deserialize(EnvDeserializer {
+        variables,
+        prefix: String::new(),
+    })
+}
+
+/// Parse a duration such as `30`, `250ms`, `1.5s`, `5m` or `1h30m`
+///
+/// Bare numbers are seconds. Supported units are `ms`, `s`, `m`, `h` and `d`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let unit_len = rest[number_len..]
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len() - number_len);
+
+        let number: f64 = rest[..number_len].parse().ok()?;
+        let unit_secs = match &rest[number_len..number_len + unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return None,
+        };
+        total += Duration::try_from_secs_f64(number * unit_secs).ok()?;
+        rest = &rest[number_len + unit_len..];
+    }
+
+    (!value.is_empty()).then_some(total)
+}
+
+/// Deserializes structs and maps from the variables under a prefix
+struct EnvDeserializer<'a> {
+    variables: &'a HashMap<String, String>,
+    prefix: String,
+}
+
+impl<'a> EnvDeserializer<'a> {
+    fn has_nested(&self, prefix: &str) -> bool {
+        self.variables.keys().any(|key| key.starts_with(prefix))
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for EnvDeserializer<'a> {
+    type Error = DotenvxError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut entries: Vec<(String, String)> = self
+            .variables
+            .iter()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix(&self.prefix)?;
+                Some((name.to_string(), value.clone()))
+            })
+            .collect();
+        entries.sort();
+
+        visitor.visit_map(EnvMapAccess {
+            prefix: self.prefix,
+            entries: entries.into_iter(),
+            current: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let prefix = self.prefix.clone();
+        visitor
+            .visit_map(StructAccess {
+                de: self,
+                fields: fields.iter(),
+                current: None,
+            })
+            .map_err(|e| match e {
+                // Name the variable the missing field would have been read from
+                DotenvxError::MissingField(field) => DotenvxError::MissingKey {
+                    key: format!("{}{}", prefix, field.to_uppercase()),
+                },
+                e => e,
+            })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+/// Where the next struct field is read from
+enum Source {
+    Value(String),
+    Nested(String),
+}
+
+struct StructAccess<'a> {
+    de: EnvDeserializer<'a>,
+    fields: std::slice::Iter<'static, &'static str>,
+    current: Option<Source>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for StructAccess<'a> {
+    type Error = DotenvxError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        // Fields without variables are skipped, so serde can apply defaults
+        for field in self.fields.by_ref() {
+            let key = format!("{}{}", self.de.prefix, field.to_uppercase());
+            let nested = format!("{}{}", key, NESTING_SEPARATOR);
+
+            self.current = if self.de.variables.contains_key(&key) {
+                Some(Source::Value(key))
+            } else if self.de.has_nested(&nested) {
+                Some(Source::Nested(nested))
+            } else {
+                continue;
+            };
+            return seed.deserialize(field.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.current.take() {
+            Some(Source::Value(key)) => {
+                let value = self.de.variables[&key].clone();
+                seed.deserialize(ValueDeserializer { key, value })
+            }
+            Some(Source::Nested(prefix)) => seed.deserialize(EnvDeserializer {
+                variables: self.de.variables,
+                prefix,
+            }),
+            None => Err(<DotenvxError as de::Error>::custom(
+                "value requested before key",
+            )),
+        }
+    }
+}
+
+struct EnvMapAccess {
+    prefix: String,
+    entries: std::vec::IntoIter<(String, String)>,
+    current: Option<(String, String)>,
+}
+
+impl<'de> de::MapAccess<'de> for EnvMapAccess {
+    type Error = DotenvxError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((name, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let key = format!("{}{}", self.prefix, name);
+        let result = seed.deserialize(ValueDeserializer {
+            key: key.clone(),
+            value: name,
+        });
+        self.current = Some((key, value));
+        result.map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (key, value) = self
+            .current
+            .take()
+            .ok_or_else(|| <DotenvxError as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer { key, value })
+    }
+}
+
+/// Deserializes a single variable's value
+struct ValueDeserializer {
+    key: String,
+    value: String,
+}
+
+impl ValueDeserializer {
+    fn invalid(&self, expected: &str) -> DotenvxError {
+        DotenvxError::InvalidValue {
+            key: self.key.clone(),
+            message: format!("expected {}, found {:?}", expected, self.value),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T> {
+        self.checked()?
+            .trim()
+            .parse()
+            .map_err(|_| self.invalid(expected))
+    }
+
+    /// The value, unless it is ciphertext that was never decrypted
+    fn checked(&self) -> Result<&str> {
+        if self.value.starts_with("encrypted:") {
+            return Err(DotenvxError::InvalidValue {
+                key: self.key.clone(),
+                message: "value is encrypted and no private key was available".to_string(),
+            });
+        }
+        Ok(&self.value)
+    }
+
+    /// Attach the variable name to errors raised by the visitor
+    fn name_errors<T>(&self, result: Result<T>) -> Result<T> {
+        result.map_err(|e| match e {
+            DotenvxError::Deserialize(message) => DotenvxError::InvalidValue {
+                key: self.key.clone(),
+                message,
+            },
+            e => e,
+        })
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                let value: $ty = self.parse(stringify!($ty))?;
+                let result = visitor.$visit(value);
+                self.name_errors(result)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = DotenvxError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = self.checked()?.to_string();
+        let result = visitor.visit_string(value);
+        self.name_errors(result)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+        deserialize_char => visit_char: char,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = match self.checked()?.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => true,
+            "false" | "0" | "no" | "off" => false,
+            _ => return Err(self.invalid("bool")),
+        };
+        let result = visitor.visit_bool(value);
+        self.name_errors(result)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.value.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let items: Vec<ValueDeserializer> = if self.checked()?.trim().is_empty() {
+            Vec::new()
+        } else {
+            self.value
+                .split(',')
+                .map(|item| ValueDeserializer {
+                    key: self.key.clone(),
+                    value: item.trim().to_string(),
+                })
+                .collect()
+        };
+        let result = visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()));
+        self.name_errors(result)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        // std::time::Duration deserializes as a struct of seconds and nanoseconds
+        if name == "Duration" && fields == ["secs", "nanos"] {
+            let duration =
+                parse_duration(self.checked()?).ok_or_else(|| self.invalid("duration"))?;
+            let parts = [
+                ("secs", duration.as_secs()),
+                ("nanos", u64::from(duration.subsec_nanos())),
+            ];
+            let result = visitor.visit_map(de::value::MapDeserializer::new(parts.into_iter()));
+            return self.name_errors(result);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let value = self.checked()?.trim().to_string();
+        let result = visitor.visit_enum(value.into_deserializer());
+        self.name_errors(result)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct tuple tuple_struct map identifier
+        ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DotenvxError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        port: u16,
+        debug: bool,
+        ratio: f64,
+        timeout: Duration,
+        hosts: Vec<String>,
+        ports: Vec<u16>,
+        db: Database,
+        cache: Option<Database>,
+        mode: Mode,
+        #[serde(default)]
+        retries: u32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Database {
+        host: String,
+        pool_size: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Development,
+        Production,
+    }
+
+    const CONTENT: &str = "PORT=8080\nDEBUG=yes\nRATIO=0.5\nTIMEOUT=1m30s\n\
+        HOSTS=a.com, b.com\nPORTS=80,443\nDB__HOST=${HOST:-localhost}\nDB__POOL_SIZE=\n\
+        MODE=production\n";
+
+    #[test]
+    fn test_from_str() {
+        let config: Config = from_str(CONTENT).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                port: 8080,
+                debug: true,
+                ratio: 0.5,
+                timeout: Duration::from_secs(90),
+                hosts: vec!["a.com".to_string(), "b.com".to_string()],
+                ports: vec![80, 443],
+                db: Database {
+                    host: "localhost".to_string(),
+                    pool_size: None,
+                },
+                cache: None,
+                mode: Mode::Production,
+                retries: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors_name_variable() {
+        let err = from_str::<Config>(&CONTENT.replace("PORT=8080", "PORT=http")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for PORT: expected u16, found \"http\""
+        );
+
+        let err = from_str::<Config>(&CONTENT.replace("PORTS=80,443", "PORTS=80,x")).unwrap_err();
+        assert!(err.to_string().starts_with("invalid value for PORTS:"));
+
+        let err = from_str::<Config>(&CONTENT.replace("MODE=production", "MODE=test")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid value for MODE: unknown variant"));
+
+        let err = from_str::<Config>(&CONTENT.replace("DB__HOST", "DB__NAME")).unwrap_err();
+        assert_eq!(err.to_string(), "missing key: DB__HOST");
+    }
+
+    #[test]
+    fn test_from_str_with_key() {
+        let keypair = crate::crypto::Keypair::generate();
+        let secret = crate::crypto::encrypt("hunter2", &keypair.public_key()).unwrap();
+        let content = format!("DB__HOST=\"{}\"\n", secret);
+
+        #[derive(Deserialize)]
+        struct Secrets {
+            db: Database,
+        }
+
+        let err = from_str::<Secrets>(&content).err().unwrap();
+        assert!(err.to_string().contains("value is encrypted"));
+
+        let secrets: Secrets = from_str_with_key(&content, &keypair.private_key()).unwrap();
+        assert_eq!(secrets.db.host, "hunter2");
+    }
+
+    #[test]
+    fn test_from_map() {
+        let vars: HashMap<String, String> = [("A".to_string(), "1".to_string())].into();
+        let map: HashMap<String, u8> = from_map(&vars).unwrap();
+        assert_eq!(map["A"], 1);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Some(Duration::from_secs(172800)));
+        assert_eq!(parse_duration("5 minutes"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+}
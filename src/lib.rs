@@ -9,6 +9,7 @@
 //! - **Key Management**: Secure public/private key handling
 //! - **Variable Expansion**: Support for `${VAR:-default}` syntax
 //! - **Command Substitution**: Execute shell commands in `.env` files
+//! - **Typed Configuration**: Read and write `.env` files as structs with serde
 //!
 //! ## Example
 //!
@@ -21,10 +22,14 @@
 
 pub mod cli;
 pub mod crypto;
+pub mod de;
 pub mod parser;
+pub mod ser;
 pub mod services;
 pub mod utils;
 
 pub use crypto::{decrypt, encrypt, Keypair};
-pub use parser::DotenvParser;
+pub use de::{from_map, from_path, from_path_with_key, from_str, from_str_with_key};
+pub use parser::{Diagnostic, DotenvParser, EnvWriter};
+pub use ser::{to_string, to_string_encrypted, to_writer};
 pub use utils::error::{DotenvxError, Result};
@@ -1,7 +1,10 @@
 use clap::Parser;
 use dotenvx::cli::args::{Cli, Commands};
 use dotenvx::cli::commands::*;
+use dotenvx::services::{ExportOptions, FormatOptions, RunOptions};
 use dotenvx::utils::logger::init_logging;
+use dotenvx::DotenvxError;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
@@ -25,19 +28,25 @@ async fn main() {
             keys_file,
             keys,
             exclude_keys,
+            schema,
             stdout,
+            cascade,
         } => encrypt_command(
             &env_files,
+            &cascade,
             keys_file.as_deref(),
             keys.as_deref(),
             exclude_keys.as_deref(),
+            schema.as_deref(),
             stdout,
+            cli.strict,
         ),
 
         Commands::Decrypt {
             env_files,
             keys_file,
-        } => decrypt_command(&env_files, keys_file.as_deref()),
+            cascade,
+        } => decrypt_command(&env_files, &cascade, keys_file.as_deref(), cli.strict),
 
         Commands::Set {
             key,
@@ -45,35 +54,222 @@ async fn main() {
             env_file,
             keys_file,
             plain,
-        } => set_command(&key, &value, &env_file, keys_file.as_deref(), plain),
+        } => set_command(
+            &key,
+            &value,
+            &env_file,
+            keys_file.as_deref(),
+            plain,
+            cli.strict,
+        ),
 
         Commands::Get {
             key,
-            env_file,
+            env_files,
             keys_file,
-        } => get_command(key.as_deref(), &env_file, keys_file.as_deref()),
+            cascade,
+        } => get_command(
+            key.as_deref(),
+            &env_files,
+            &cascade,
+            keys_file.as_deref(),
+            cli.strict,
+        ),
 
         Commands::Ls { directory } => ls_command(&directory),
 
+        Commands::Fmt {
+            env_files,
+            check,
+            quote,
+            export,
+            sort,
+        } => fmt_command(
+            &env_files,
+            check,
+            &FormatOptions {
+                quote,
+                export,
+                sort,
+            },
+            cli.strict,
+        ),
+
+        Commands::Lint {
+            env_files,
+            fix,
+            rules,
+            format,
+        } => lint_command(&env_files, fix, &rules, format),
+
+        Commands::Export {
+            env_files,
+            keys_file,
+            to,
+            name,
+            namespace,
+            secret_keys,
+            sealed,
+            eval,
+        } => export_command(
+            &env_files,
+            keys_file.as_deref(),
+            &ExportOptions {
+                target: to,
+                name,
+                namespace,
+                secret_keys,
+            },
+            sealed,
+            cli.strict,
+            &eval,
+        ),
+
+        Commands::Explain {
+            key,
+            env,
+            env_files,
+            keys_file,
+            overload,
+            clean,
+            pass,
+            reveal,
+            cascade,
+            eval,
+        } => explain_command(
+            key.as_deref(),
+            &env,
+            &env_files,
+            &cascade,
+            keys_file.as_deref(),
+            &RunOptions {
+                overload,
+                clean,
+                pass,
+                reveal,
+                ..RunOptions::default()
+            },
+            cli.strict,
+            &eval,
+        ),
+
+        Commands::Validate {
+            env_files,
+            keys_file,
+            schema,
+            cascade,
+            eval,
+        } => validate_command(
+            &env_files,
+            &cascade,
+            keys_file.as_deref(),
+            schema.as_deref(),
+            cli.strict,
+            &eval,
+        ),
+
+        Commands::Printenv {
+            env_files,
+            keys_file,
+            format,
+            cascade,
+            eval,
+        } => printenv_command(
+            &env_files,
+            &cascade,
+            keys_file.as_deref(),
+            format,
+            cli.strict,
+            &eval,
+        ),
+
+        Commands::Import {
+            file,
+            from,
+            from_env,
+            env_file,
+            keys_file,
+            separator,
+            replace,
+            plain,
+        } => import_command(
+            file.as_deref(),
+            from,
+            from_env.as_deref(),
+            &env_file,
+            keys_file.as_deref(),
+            &separator,
+            replace,
+            plain,
+            cli.strict,
+        ),
+
         Commands::Run {
-            env: _,
+            env,
             env_files,
             keys_file,
             overload,
+            grace_period,
+            exec,
+            no_exec,
+            watch,
+            redact,
+            clean,
+            pass,
+            as_files,
+            as_files_pattern,
+            explain,
+            reveal,
+            validate,
+            schema,
+            cascade,
+            eval,
             command,
         } => {
-            let exit_code = run_command(&env_files, keys_file.as_deref(), overload, &command)
-                .await
-                .unwrap_or_else(|e| {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                });
+            let exit_code = run_command(
+                &env,
+                &env_files,
+                &cascade,
+                keys_file.as_deref(),
+                validate.then_some(schema.as_deref()),
+                RunOptions {
+                    overload,
+                    grace_period: (grace_period > 0).then(|| Duration::from_secs(grace_period)),
+                    exec: (exec || no_exec).then_some(exec),
+                    watch,
+                    schema: None,
+                    redact,
+                    clean,
+                    pass,
+                    explain,
+                    reveal,
+                    as_files,
+                    as_files_patterns: as_files_pattern,
+                    ..RunOptions::default()
+                },
+                cli.strict,
+                &eval,
+                &command,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                report_error(&e);
+                std::process::exit(1);
+            });
             std::process::exit(exit_code);
         }
     };
 
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
+        report_error(&e);
         std::process::exit(1);
     }
 }
+
+fn report_error(error: &DotenvxError) {
+    match error {
+        // Diagnostics are already rendered with their own severity labels
+        DotenvxError::InvalidEnvFile { .. } => eprintln!("{}", error),
+        _ => eprintln!("Error: {}", error),
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The file is usable, but probably not as intended
+    Warning,
+    /// The file cannot be loaded
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while parsing a .env file, located by line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// File the problem was found in, if known
+    pub path: Option<PathBuf>,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column number (1-based, in characters)
+    pub column: usize,
+    /// Number of characters the problem spans, at least 1
+    pub length: usize,
+    /// How serious the problem is
+    pub severity: Severity,
+    /// Short name of the check that produced it, such as `duplicate-key`
+    pub code: Option<String>,
+    /// Description of the problem
+    pub message: String,
+    /// The offending source line
+    pub excerpt: String,
+}
+
+impl Diagnostic {
+    /// Create an error diagnostic
+    pub fn error(line: usize, column: usize, length: usize, message: &str, excerpt: &str) -> Self {
+        Self {
+            path: None,
+            line,
+            column,
+            length: length.max(1),
+            severity: Severity::Error,
+            code: None,
+            message: message.to_string(),
+            excerpt: excerpt.to_string(),
+        }
+    }
+
+    /// Create a warning diagnostic
+    pub fn warning(
+        line: usize,
+        column: usize,
+        length: usize,
+        message: &str,
+        excerpt: &str,
+    ) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(line, column, length, message, excerpt)
+        }
+    }
+
+    /// Whether this diagnostic prevents the file from loading
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Render in the style of rustc:
+    ///
+    /// ```text
+    /// error[syntax]: Missing '=' in variable assignment
+    ///  --> .env:3:1
+    ///   |
+    /// 3 | INVALID
+    ///   | ^^^^^^^
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self
+            .path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<input>".to_string());
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        match &self.code {
+            Some(code) => writeln!(f, "{}[{}]: {}", self.severity, code, self.message)?,
+            None => writeln!(f, "{}: {}", self.severity, self.message)?,
+        }
+        writeln!(f, "{}--> {}:{}:{}", gutter, path, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.excerpt)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column.saturating_sub(1)),
+            "^".repeat(self.length)
+        )
+    }
+}
+
+/// Log the warnings among `diagnostics`, leaving errors to the caller
+pub fn log_warnings(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics.iter().filter(|d| !d.is_error()) {
+        tracing::warn!("{}", diagnostic);
+    }
+}
+
+/// Render diagnostics one after another, followed by a summary line
+pub fn render_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        output.push_str(&diagnostic.to_string());
+        output.push_str("\n\n");
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    if errors > 0 {
+        output.push_str(&format!(
+            "error: could not load .env file due to {} previous error{}",
+            errors,
+            plural(errors)
+        ));
+        if warnings > 0 {
+            output.push_str(&format!(
+                "; {} warning{} emitted",
+                warnings,
+                plural(warnings)
+            ));
+        }
+    } else {
+        output.push_str(&format!(
+            "warning: {} warning{} emitted",
+            warnings,
+            plural(warnings)
+        ));
+    }
+
+    output
+}
+
+/// A variable that does not satisfy its schema field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Variable name
+    pub key: String,
+    /// What is wrong, without the value itself
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Render violations as one line each, under a summary
+pub fn render_violations(violations: &[Violation]) -> String {
+    let mut output = format!(
+        "environment does not match the schema ({} problem(s))",
+        violations.len()
+    );
+    for violation in violations {
+        output.push_str("\n  ");
+        output.push_str(&violation.to_string());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_diagnostic() {
+        let mut diagnostic = Diagnostic::error(12, 3, 4, "bad key", "  BAD KEY=1");
+        diagnostic.path = Some(PathBuf::from(".env"));
+
+        let expected = "error: bad key\n  --> .env:12:3\n   |\n12 |   BAD KEY=1\n   |   ^^^^";
+        assert_eq!(diagnostic.to_string(), expected);
+    }
+
+    #[test]
+    fn test_render_summary() {
+        let diagnostics = vec![
+            Diagnostic::error(1, 1, 1, "first", "A"),
+            Diagnostic::error(2, 1, 1, "second", "B"),
+            Diagnostic::warning(3, 1, 1, "third", "C"),
+        ];
+
+        let rendered = render_diagnostics(&diagnostics);
+        assert!(rendered.contains("error: first"));
+        assert!(rendered.contains("error: second"));
+        assert!(rendered.contains("warning: third"));
+        assert!(rendered.ends_with("due to 2 previous errors; 1 warning emitted"));
+    }
+}
@@ -0,0 +1,268 @@
+use crate::parser::dotenv::{DotenvParser, Quote};
+use std::fmt;
+
+/// A .env file as written, line by line
+///
+/// Unlike [`DotenvParser`], which only keeps the resulting variables, a
+/// document keeps every comment, blank line, spacing choice and quote, so that
+/// `Document::parse(s).to_string() == s` for any input. Tools that rewrite
+/// files (lint fixes, the formatter) edit a document instead of splicing text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    /// The lines of the file, without line terminators
+    pub lines: Vec<Line>,
+    /// Line terminator used when rendering, `"\n"` or `"\r\n"`
+    pub newline: String,
+    /// Whether the last line is followed by a terminator
+    pub final_newline: bool,
+}
+
+/// A single line of a [`Document`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    /// An empty or whitespace-only line, as written
+    Blank(String),
+    /// A comment line, as written
+    Comment(String),
+    /// A `KEY=value` assignment
+    Entry(EntryLine),
+    /// A line that is not a valid assignment, as written
+    Invalid(String),
+}
+
+/// A `KEY=value` line split into its parts
+///
+/// Rendering concatenates `prefix`, `key`, `before_eq`, `=`, `after_eq`,
+/// `raw_value` and `trailing`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryLine {
+    /// Indentation and optional `export ` keyword before the key
+    pub prefix: String,
+    /// Variable name
+    pub key: String,
+    /// Whitespace between the key and `=`
+    pub before_eq: String,
+    /// Whitespace between `=` and the value
+    pub after_eq: String,
+    /// The value as written, including any quotes
+    pub raw_value: String,
+    /// Whitespace after the value
+    pub trailing: String,
+}
+
+impl EntryLine {
+    /// Create an assignment with no extra spacing
+    pub fn new(key: &str, raw_value: &str) -> Self {
+        Self {
+            prefix: String::new(),
+            key: key.to_string(),
+            before_eq: String::new(),
+            after_eq: String::new(),
+            raw_value: raw_value.to_string(),
+            trailing: String::new(),
+        }
+    }
+
+    /// Whether the assignment uses the `export` keyword
+    pub fn is_exported(&self) -> bool {
+        self.prefix.trim() == "export"
+    }
+
+    /// The parsed value and how it was quoted, exactly as [`DotenvParser`] reads it
+    pub fn value(&self) -> (String, Quote) {
+        DotenvParser::parse_value(&self.raw_value)
+    }
+}
+
+impl fmt::Display for EntryLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}={}{}{}",
+            self.prefix, self.key, self.before_eq, self.after_eq, self.raw_value, self.trailing
+        )
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blank(raw) | Self::Comment(raw) | Self::Invalid(raw) => write!(f, "{}", raw),
+            Self::Entry(entry) => write!(f, "{}", entry),
+        }
+    }
+}
+
+impl Line {
+    fn parse(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return Self::Blank(raw.to_string());
+        }
+        if trimmed.starts_with('#') {
+            return Self::Comment(raw.to_string());
+        }
+
+        // Mirror DotenvParser::parse_line: optional `export `, then KEY=value
+        let indent_len = raw.len() - raw.trim_start().len();
+        let rest = &raw[indent_len..];
+        let prefix_len = match rest.strip_prefix("export ") {
+            Some(stripped) => indent_len + rest.len() - stripped.trim_start().len(),
+            None => indent_len,
+        };
+        let body = &raw[prefix_len..];
+
+        let Some(eq_pos) = body.find('=') else {
+            return Self::Invalid(raw.to_string());
+        };
+        let key_part = &body[..eq_pos];
+        let key = key_part.trim_end();
+        if key.is_empty() || key.trim_start().len() != key.len() {
+            return Self::Invalid(raw.to_string());
+        }
+
+        let value_part = &body[eq_pos + 1..];
+        let value_start = value_part.len() - value_part.trim_start().len();
+        let value = value_part.trim();
+        let trailing_start = value_start + value.len();
+
+        Self::Entry(EntryLine {
+            prefix: raw[..prefix_len].to_string(),
+            key: key.to_string(),
+            before_eq: key_part[key.len()..].to_string(),
+            after_eq: value_part[..value_start].to_string(),
+            raw_value: value.to_string(),
+            trailing: value_part[trailing_start..].to_string(),
+        })
+    }
+}
+
+impl Document {
+    /// Split .env content into lines without losing any formatting
+    pub fn parse(content: &str) -> Self {
+        // Files with mixed line endings keep their stray '\r' inside the lines
+        let crlf = content.contains('\n')
+            && content.matches('\n').count() == content.matches("\r\n").count();
+        let newline = if crlf { "\r\n" } else { "\n" };
+        let final_newline = content.ends_with('\n');
+        let body = content.strip_suffix(newline).unwrap_or(content);
+
+        let lines = if content.is_empty() {
+            Vec::new()
+        } else {
+            body.split(newline).map(Line::parse).collect()
+        };
+
+        Self {
+            lines,
+            newline: newline.to_string(),
+            final_newline,
+        }
+    }
+
+    /// Iterate over assignments together with their line numbers (1-based)
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &EntryLine)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match line {
+                Line::Entry(entry) => Some((i + 1, entry)),
+                _ => None,
+            })
+    }
+
+    /// Sort each run of consecutive assignments by key
+    ///
+    /// Comments and blank lines stay where they are and delimit the runs, so
+    /// a comment heading a group of keys keeps heading the same group.
+    pub fn sort_sections(&mut self) {
+        let mut start = 0;
+        while start < self.lines.len() {
+            let end = self.lines[start..]
+                .iter()
+                .position(|line| !matches!(line, Line::Entry(_)))
+                .map_or(self.lines.len(), |n| start + n);
+
+            self.lines[start..end].sort_by(|a, b| match (a, b) {
+                (Line::Entry(a), Line::Entry(b)) => a.key.cmp(&b.key),
+                _ => std::cmp::Ordering::Equal,
+            });
+            start = end + 1;
+        }
+    }
+
+    /// Iterate mutably over assignments
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut EntryLine> {
+        self.lines.iter_mut().filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", self.newline)?;
+            }
+            write!(f, "{}", line)?;
+        }
+        if self.final_newline && !self.lines.is_empty() {
+            write!(f, "{}", self.newline)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let inputs = [
+            "",
+            "\n",
+            "KEY=value",
+            "# comment\n\n  export   KEY =  \"a b\"   \nbad line\r\nX='y'\n",
+            "A=1\r\nB=2\r\n",
+            "=oops\n  \t\nexport\n",
+        ];
+        for input in inputs {
+            assert_eq!(Document::parse(input).to_string(), input);
+        }
+    }
+
+    #[test]
+    fn test_entry_parts() {
+        let doc = Document::parse("  export   KEY =  \"a b\"   ");
+        let (line, entry) = doc.entries().next().unwrap();
+        assert_eq!(line, 1);
+        assert_eq!(entry.prefix, "  export   ");
+        assert!(entry.is_exported());
+        assert_eq!(entry.key, "KEY");
+        assert_eq!(entry.before_eq, " ");
+        assert_eq!(entry.after_eq, "  ");
+        assert_eq!(entry.raw_value, "\"a b\"");
+        assert_eq!(entry.trailing, "   ");
+        assert_eq!(entry.value(), ("a b".to_string(), Quote::Double));
+    }
+
+    #[test]
+    fn test_sort_sections() {
+        let mut doc = Document::parse("C=1\nA=2\n# b\nZ=1\nB=2\n\nY=1\nX=2");
+        doc.sort_sections();
+        assert_eq!(doc.to_string(), "A=2\nC=1\n# b\nB=2\nZ=1\n\nX=2\nY=1");
+    }
+
+    #[test]
+    fn test_line_kinds() {
+        let doc = Document::parse("# c\n\nA=1\nINVALID\n=x");
+        assert!(matches!(doc.lines[0], Line::Comment(_)));
+        assert!(matches!(doc.lines[1], Line::Blank(_)));
+        assert!(matches!(doc.lines[2], Line::Entry(_)));
+        assert!(matches!(doc.lines[3], Line::Invalid(_)));
+        assert!(matches!(doc.lines[4], Line::Invalid(_)));
+    }
+}
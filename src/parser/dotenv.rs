@@ -1,11 +1,72 @@
-use crate::parser::{expand_variables, substitute_commands};
+use crate::parser::diagnostic::{Diagnostic, Severity};
+use crate::parser::expand_with;
+use crate::parser::substitution::{
+    has_substitution, substitute_commands_with, CommandContext, SubstitutionPolicy,
+};
 use crate::utils::error::{DotenvxError, Result};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// How a value was quoted in the source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    /// Bare value
+    None,
+    /// `'value'` - taken literally, never expanded
+    Single,
+    /// `"value"` - escape sequences are processed
+    Double,
+    /// `` `command` `` - command substitution
+    Backtick,
+}
+
+/// A single `KEY=value` assignment, in file order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Variable name
+    pub key: String,
+    /// Parsed value
+    pub value: String,
+    /// Quoting used for the value
+    pub quote: Quote,
+    /// Line number (1-based) of the assignment
+    pub line: usize,
+    /// Whether the value was stored as `encrypted:` ciphertext
+    ///
+    /// Such values are never substituted or expanded, even once decrypted.
+    pub encrypted: bool,
+}
+
+/// Whether `key` is a POSIX identifier: `[A-Za-z_][A-Za-z0-9_]*`
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Entry {
+    /// Whether the value is used verbatim, without substitution or expansion
+    pub fn is_literal(&self) -> bool {
+        self.quote == Quote::Single || self.encrypted
+    }
+
+    /// Whether the value is a command substitution, `$(...)` or backticks
+    pub fn has_command(&self) -> bool {
+        !self.is_literal() && (self.quote == Quote::Backtick || has_substitution(&self.value))
+    }
+}
 
 /// Parser for .env files
 #[derive(Debug, Default)]
 pub struct DotenvParser {
     variables: HashMap<String, String>,
+    entries: Vec<Entry>,
+    diagnostics: Vec<Diagnostic>,
+    path: Option<PathBuf>,
+    strict_keys: bool,
+    duplicates: Option<Severity>,
 }
 
 impl DotenvParser {
@@ -14,8 +75,38 @@ impl DotenvParser {
         Self::default()
     }
 
+    /// Create a parser whose diagnostics refer to `path`
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Reject keys that are not POSIX identifiers and duplicate keys
+    ///
+    /// Outside strict mode both are reported as warnings.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict_keys = strict;
+        self.duplicates = strict.then_some(Severity::Error);
+        self
+    }
+
+    /// Override how duplicate keys within the file are reported
+    pub fn duplicates(mut self, severity: Severity) -> Self {
+        self.duplicates = Some(severity);
+        self
+    }
+
     /// Parse a .env file content
     ///
+    /// Parsing never executes anything: backtick values are kept as the literal
+    /// command text and `$(...)` is left in place until [`Self::substitute`] is
+    /// called.
+    ///
+    /// Every line is checked before returning, so a file with several problems
+    /// reports all of them at once through [`DotenvxError::InvalidEnvFile`].
+    ///
     /// # Arguments
     ///
     /// * `content` - The content of the .env file
@@ -27,9 +118,36 @@ impl DotenvParser {
         for (line_num, line) in content.lines().enumerate() {
             self.parse_line(line, line_num + 1)?;
         }
+
+        if self.diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(DotenvxError::InvalidEnvFile {
+                diagnostics: self.diagnostics.clone(),
+            });
+        }
         Ok(&self.variables)
     }
 
+    /// Record a diagnostic for `line`, spanning `(offset, length)`: `length`
+    /// characters starting at byte offset `offset`
+    fn report(
+        &mut self,
+        code: &str,
+        severity: Severity,
+        line: &str,
+        line_num: usize,
+        (offset, length): (usize, usize),
+        message: &str,
+    ) {
+        let column = line[..offset].chars().count() + 1;
+        let mut diagnostic = match severity {
+            Severity::Error => Diagnostic::error(line_num, column, length, message, line),
+            Severity::Warning => Diagnostic::warning(line_num, column, length, message, line),
+        };
+        diagnostic.path = self.path.clone();
+        diagnostic.code = Some(code.to_string());
+        self.diagnostics.push(diagnostic);
+    }
+
     /// Parse a single line
     fn parse_line(&mut self, line: &str, line_num: usize) -> Result<()> {
         let trimmed = line.trim();
@@ -45,13 +163,21 @@ impl DotenvParser {
         } else {
             trimmed
         };
+        let content_offset =
+            line.len() - line.trim_start().len() + trimmed.len() - line_content.len();
 
         // Find the = separator
         let Some(eq_pos) = line_content.find('=') else {
-            return Err(DotenvxError::ParseError {
-                line: line_num,
-                message: "Missing '=' in variable assignment".to_string(),
-            });
+            let length = line_content.chars().count();
+            self.report(
+                "syntax",
+                Severity::Error,
+                line,
+                line_num,
+                (content_offset, length),
+                "Missing '=' in variable assignment",
+            );
+            return Ok(());
         };
 
         let key = line_content[..eq_pos].trim();
@@ -59,50 +185,96 @@ impl DotenvParser {
 
         // Validate key
         if key.is_empty() {
-            return Err(DotenvxError::ParseError {
-                line: line_num,
-                message: "Empty variable name".to_string(),
-            });
+            self.report(
+                "syntax",
+                Severity::Error,
+                line,
+                line_num,
+                (content_offset + eq_pos, 1),
+                "Empty variable name",
+            );
+            return Ok(());
         }
 
-        // Parse value (handle quotes)
-        let value = self.parse_value(value_part)?;
+        let key_offset = content_offset + line_content[..eq_pos].find(key).unwrap_or(0);
+        if !is_valid_key(key) {
+            let severity = if self.strict_keys {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            self.report(
+                "invalid-key",
+                severity,
+                line,
+                line_num,
+                (key_offset, key.chars().count()),
+                &format!(
+                    "Invalid variable name '{}': use letters, digits and underscores, not starting with a digit",
+                key
+                ),
+            );
+        }
 
-        self.variables.insert(key.to_string(), value);
+        if let Some(first) = self.entries.iter().find(|e| e.key == key) {
+            let message = format!(
+                "Duplicate key '{}' (first defined on line {})",
+                key, first.line
+            );
+            self.report(
+                "duplicate-key",
+                self.duplicates.unwrap_or(Severity::Warning),
+                line,
+                line_num,
+                (key_offset, key.chars().count()),
+                &message,
+            );
+        }
+
+        // Parse value (handle quotes)
+        let (value, quote) = Self::parse_value(value_part);
+
+        self.variables.insert(key.to_string(), value.clone());
+        self.entries.push(Entry {
+            key: key.to_string(),
+            encrypted: value.starts_with("encrypted:"),
+            value,
+            quote,
+            line: line_num,
+        });
         Ok(())
     }
 
     /// Parse a value, handling quotes and escapes
-    fn parse_value(&self, value: &str) -> Result<String> {
+    pub(crate) fn parse_value(value: &str) -> (String, Quote) {
         if value.is_empty() {
-            return Ok(String::new());
+            return (String::new(), Quote::None);
         }
 
         let value = value.trim();
 
         // Handle single quotes (no expansion)
         if value.starts_with('\'') && value.ends_with('\'') && value.len() >= 2 {
-            return Ok(value[1..value.len() - 1].to_string());
+            return (value[1..value.len() - 1].to_string(), Quote::Single);
         }
 
         // Handle double quotes (with expansion)
         if value.starts_with('"') && value.ends_with('"') && value.len() >= 2 {
             let inner = &value[1..value.len() - 1];
-            return Ok(self.unescape(inner));
+            return (Self::unescape(inner), Quote::Double);
         }
 
-        // Handle backticks (command substitution)
+        // Handle backticks (command substitution, evaluated by `substitute`)
         if value.starts_with('`') && value.ends_with('`') && value.len() >= 2 {
-            let command = &value[1..value.len() - 1];
-            return substitute_commands(&format!("$({})", command));
+            return (value[1..value.len() - 1].to_string(), Quote::Backtick);
         }
 
         // No quotes - return as is
-        Ok(value.to_string())
+        (value.to_string(), Quote::None)
     }
 
     /// Unescape special characters in a string
-    fn unescape(&self, s: &str) -> String {
+    pub(crate) fn unescape(s: &str) -> String {
         let mut result = String::new();
         let mut chars = s.chars();
 
@@ -132,33 +304,133 @@ impl DotenvParser {
         result
     }
 
+    /// Escape a value for use inside double quotes, the inverse of `unescape`
+    pub(crate) fn escape(s: &str) -> String {
+        let mut result = String::with_capacity(s.len());
+
+        for ch in s.chars() {
+            match ch {
+                '\\' => result.push_str("\\\\"),
+                '"' => result.push_str("\\\""),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                _ => result.push(ch),
+            }
+        }
+
+        result
+    }
+
     /// Get the parsed variables
     pub fn variables(&self) -> &HashMap<String, String> {
         &self.variables
     }
 
+    /// Get the diagnostics found while parsing, in line order
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Get the parsed assignments in file order, including duplicates
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Replace the value of the entry at `index`, keeping the variables map in sync
+    pub fn set_value(&mut self, index: usize, value: String) {
+        let entry = &mut self.entries[index];
+        entry.value = value;
+
+        // Only the last assignment of a key is visible through `variables()`
+        let is_last = !self.entries[index + 1..]
+            .iter()
+            .any(|e| e.key == self.entries[index].key);
+        if is_last {
+            let entry = &self.entries[index];
+            self.variables
+                .insert(entry.key.clone(), entry.value.clone());
+        }
+    }
+
     /// Expand all variables in the parsed values
+    ///
+    /// Entries are expanded in file order, so a value sees the expanded form of
+    /// the variables assigned before it. Single-quoted values are left as-is.
     pub fn expand(&mut self) -> Result<()> {
-        let keys: Vec<String> = self.variables.keys().cloned().collect();
+        self.expand_with(|_| None, |_| None)
+    }
 
-        for key in keys {
-            let value = self.variables.get(&key).unwrap().clone();
-            let expanded = expand_variables(&value, &self.variables)?;
-            self.variables.insert(key, expanded);
+    /// Expand all variables, resolving names through additional layers
+    ///
+    /// A reference is looked up in `overrides` first, then in this file, then
+    /// in `fallback`.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - Lookup for variables that take precedence over the file
+    /// * `fallback` - Lookup for variables used when the file does not define them
+    pub fn expand_with<O, F>(&mut self, overrides: O, fallback: F) -> Result<()>
+    where
+        O: Fn(&str) -> Option<String>,
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut visible = self.variables.clone();
+
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            if entry.is_literal() {
+                visible.insert(entry.key.clone(), entry.value.clone());
+                continue;
+            }
+
+            let expanded = expand_with(&entry.value, |name| {
+                overrides(name)
+                    .or_else(|| visible.get(name).cloned())
+                    .or_else(|| fallback(name))
+            })?;
+            visible.insert(entry.key.clone(), expanded.clone());
+            self.set_value(index, expanded);
         }
 
         Ok(())
     }
 
     /// Perform command substitution on all values
+    ///
+    /// This runs backtick values and every `$(...)` as shell commands, so it
+    /// must only be called on behalf of commands that are meant to evaluate the
+    /// file, such as `run`.
     pub fn substitute(&mut self) -> Result<()> {
-        let keys: Vec<String> = self.variables.keys().cloned().collect();
+        self.substitute_with(&SubstitutionPolicy::default(), &CommandContext::default())
+    }
+
+    /// Perform command substitution on all values under a policy
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Limits applied to every command
+    /// * `context` - Directory and environment the commands run with
+    pub fn substitute_with(
+        &mut self,
+        policy: &SubstitutionPolicy,
+        context: &CommandContext,
+    ) -> Result<()> {
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            if entry.is_literal() {
+                continue;
+            }
 
-        for key in keys {
-            let value = self.variables.get(&key).unwrap().clone();
-            if value.contains("$(") {
-                let substituted = substitute_commands(&value)?;
-                self.variables.insert(key, substituted);
+            if entry.quote == Quote::Backtick {
+                let command = format!("$({})", entry.value);
+                let substituted = substitute_commands_with(&command, policy, context)?;
+                if policy.enabled {
+                    self.set_value(index, substituted);
+                }
+            } else if has_substitution(&entry.value) {
+                let substituted = substitute_commands_with(&entry.value, policy, context)?;
+                self.set_value(index, substituted);
             }
         }
 
@@ -262,6 +534,63 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_reports_all_errors() {
+        let mut parser = DotenvParser::with_path(".env");
+        let content = "GOOD=1\nINVALID\n  export  =value\nALSO_GOOD=2\nbad line";
+        let err = parser.parse(content).unwrap_err();
+
+        let DotenvxError::InvalidEnvFile { diagnostics } = err else {
+            panic!("expected InvalidEnvFile");
+        };
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 1));
+        assert_eq!(diagnostics[0].length, 7);
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (3, 11));
+        assert_eq!(diagnostics[1].message, "Empty variable name");
+        assert_eq!(diagnostics[2].excerpt, "bad line");
+        assert_eq!(
+            diagnostics[2].path.as_deref(),
+            Some(std::path::Path::new(".env"))
+        );
+        assert_eq!(parser.diagnostics().len(), 3);
+        assert_eq!(parser.variables().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let content = "GOOD_1=a\nmy-key=b\nmy.key=c\n2FA=d\nWITH SPACE=e";
+
+        let mut parser = DotenvParser::new();
+        parser.parse(content).unwrap();
+        assert_eq!(parser.diagnostics().len(), 4);
+        assert!(parser.diagnostics().iter().all(|d| !d.is_error()));
+
+        let mut parser = DotenvParser::new().strict(true);
+        let err = parser.parse(content).unwrap_err();
+        let DotenvxError::InvalidEnvFile { diagnostics } = err else {
+            panic!("expected InvalidEnvFile");
+        };
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let content = "A=1\nB=2\n  export A=3";
+
+        let mut parser = DotenvParser::new();
+        let vars = parser.parse(content).unwrap();
+        assert_eq!(vars.get("A").unwrap(), "3");
+        let diagnostic = &parser.diagnostics()[0];
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!((diagnostic.line, diagnostic.column), (3, 10));
+        assert!(diagnostic.message.contains("first defined on line 1"));
+
+        let mut parser = DotenvParser::new().duplicates(Severity::Error);
+        assert!(parser.parse(content).is_err());
+    }
+
     #[test]
     fn test_expand_variables() {
         let mut parser = DotenvParser::new();
@@ -283,6 +612,27 @@ mod tests {
         assert_eq!(parser.variables().get("URL").unwrap(), "localhost:3000");
     }
 
+    #[test]
+    fn test_expand_single_quotes_literal() {
+        let mut parser = DotenvParser::new();
+        let content = "HOST=localhost\nLITERAL='$HOST'";
+        parser.parse(content).unwrap();
+        parser.expand().unwrap();
+        assert_eq!(parser.variables().get("LITERAL").unwrap(), "$HOST");
+    }
+
+    #[test]
+    fn test_expand_with_layers() {
+        let mut parser = DotenvParser::new();
+        let content = "HOST=file\nURL=$HOST/$PORT";
+        parser.parse(content).unwrap();
+        parser
+            .expand_with(|_| None, |name| (name == "PORT").then(|| "80".to_string()))
+            .unwrap();
+        assert_eq!(parser.variables().get("URL").unwrap(), "file/80");
+        assert_eq!(parser.entries()[1].line, 2);
+    }
+
     #[test]
     fn test_command_substitution() {
         let mut parser = DotenvParser::new();
@@ -292,6 +642,35 @@ mod tests {
         assert_eq!(parser.variables().get("RESULT").unwrap(), "test");
     }
 
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_parse_does_not_execute() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let marker = temp.path().join("marker");
+        let content = format!("A=`touch {0}`\nB=$(touch {0})", marker.display());
+
+        let mut parser = DotenvParser::new();
+        parser.parse(&content).unwrap();
+        assert!(!marker.exists());
+        assert_eq!(parser.entries()[0].quote, Quote::Backtick);
+        assert_eq!(
+            parser.variables().get("A").unwrap(),
+            &format!("touch {}", marker.display())
+        );
+
+        parser.substitute().unwrap();
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        let mut parser = DotenvParser::new();
+        parser.parse("RESULT=`echo test`").unwrap();
+        assert_eq!(parser.variables().get("RESULT").unwrap(), "echo test");
+        parser.substitute().unwrap();
+        assert_eq!(parser.variables().get("RESULT").unwrap(), "test");
+    }
+
     #[test]
     fn test_parse_with_processing() {
         let mut parser = DotenvParser::new();
@@ -299,4 +678,19 @@ mod tests {
         parser.parse_with_processing(content).unwrap();
         assert_eq!(parser.variables().get("PATH").unwrap(), "/tmp/subdir");
     }
+
+    #[test]
+    fn test_escape_roundtrip() {
+        for value in [
+            "plain",
+            "a \"quoted\" word",
+            "C:\\dir\\new",
+            "two\nlines\ttab",
+            "it's",
+        ] {
+            let escaped = DotenvParser::escape(value);
+            assert!(!escaped.contains('\n'));
+            assert_eq!(DotenvParser::unescape(&escaped), value);
+        }
+    }
 }
@@ -9,6 +9,7 @@ use std::collections::HashMap;
 /// - `${VAR:-default}` - variable with default value
 /// - `${VAR:+alternate}` - alternate value if variable exists
 /// - `$VAR` - simple variable expansion (without braces)
+/// - `\$` - a literal `$`
 ///
 /// # Arguments
 ///
@@ -19,58 +20,74 @@ use std::collections::HashMap;
 ///
 /// The expanded value string
 pub fn expand_variables(value: &str, env: &HashMap<String, String>) -> Result<String> {
-    let mut result = value.to_string();
-
-    // Pattern for ${VAR:-default} or ${VAR:+alternate} or ${VAR}
-    let re_braces = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(:-|:\+)?([^}]*)\}")
-        .map_err(|e| DotenvxError::VariableExpansion(e.to_string()))?;
-
-    // Pattern for $VAR (without braces)
-    let re_simple = Regex::new(r"\$([A-Za-z_][A-Za-z0-9_]*)")
-        .map_err(|e| DotenvxError::VariableExpansion(e.to_string()))?;
-
-    // First, handle ${VAR} patterns with operators
-    result = re_braces
-        .replace_all(&result, |caps: &regex::Captures| {
-            let var_name = &caps[1];
-            let operator = caps.get(2).map(|m| m.as_str());
-            let operand = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-
-            match operator {
-                Some(":-") => {
-                    // ${VAR:-default} - use default if variable is unset or empty
-                    env.get(var_name)
-                        .filter(|v| !v.is_empty())
-                        .map(|v| v.as_str())
-                        .unwrap_or(operand)
-                        .to_string()
-                }
-                Some(":+") => {
-                    // ${VAR:+alternate} - use alternate if variable is set and non-empty
-                    if env.get(var_name).filter(|v| !v.is_empty()).is_some() {
-                        operand.to_string()
-                    } else {
-                        String::new()
-                    }
-                }
-                None => {
-                    // ${VAR} - simple expansion
-                    env.get(var_name).cloned().unwrap_or_default()
+    expand_with(value, |name| env.get(name).cloned())
+}
+
+/// Expand variables in a value string, resolving names through a lookup function
+///
+/// This is the building block for layered expansion, where a reference may be
+/// satisfied by the process environment, earlier files, or the current file
+/// depending on the configured precedence. The supported syntaxes are the same
+/// as for [`expand_variables`], and the value is scanned once so that expanded
+/// text is never expanded again.
+///
+/// # Arguments
+///
+/// * `value` - The value string to expand
+/// * `lookup` - Returns the value of a variable, or `None` if it is unset
+///
+/// # Returns
+///
+/// The expanded value string
+pub fn expand_with<F>(value: &str, lookup: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    // Pattern for \$, ${VAR:-default}, ${VAR:+alternate}, ${VAR} or $VAR
+    let re = Regex::new(
+        r"\\\$|\$\{([A-Za-z_][A-Za-z0-9_]*)(:-|:\+)?([^}]*)\}|\$([A-Za-z_][A-Za-z0-9_]*)",
+    )
+    .map_err(|e| DotenvxError::VariableExpansion(e.to_string()))?;
+
+    let result = re.replace_all(value, |caps: &regex::Captures| {
+        if &caps[0] == "\\$" {
+            // \$ - an escaped dollar sign, kept literally
+            return "$".to_string();
+        }
+
+        if let Some(simple) = caps.get(4) {
+            // $VAR - simple expansion (without braces)
+            return lookup(simple.as_str()).unwrap_or_default();
+        }
+
+        let var_name = &caps[1];
+        let operator = caps.get(2).map(|m| m.as_str());
+        let operand = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+
+        match operator {
+            Some(":-") => {
+                // ${VAR:-default} - use default if variable is unset or empty
+                lookup(var_name)
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or_else(|| operand.to_string())
+            }
+            Some(":+") => {
+                // ${VAR:+alternate} - use alternate if variable is set and non-empty
+                if lookup(var_name).filter(|v| !v.is_empty()).is_some() {
+                    operand.to_string()
+                } else {
+                    String::new()
                 }
-                _ => caps[0].to_string(),
             }
-        })
-        .to_string();
-
-    // Then handle $VAR patterns (without braces)
-    result = re_simple
-        .replace_all(&result, |caps: &regex::Captures| {
-            let var_name = &caps[1];
-            env.get(var_name).cloned().unwrap_or_default()
-        })
-        .to_string();
+            None => {
+                // ${VAR} - simple expansion
+                lookup(var_name).unwrap_or_default()
+            }
+            _ => caps[0].to_string(),
+        }
+    });
 
-    Ok(result)
+    Ok(result.into_owned())
 }
 
 #[cfg(test)]
@@ -154,4 +171,28 @@ mod tests {
         let result = expand_variables("plain text", &env).unwrap();
         assert_eq!(result, "plain text");
     }
+
+    #[test]
+    fn test_expanded_values_are_not_reexpanded() {
+        let env = make_env(&[("PROMPT", "$USER>"), ("USER", "alice")]);
+        let result = expand_variables("${PROMPT}", &env).unwrap();
+        assert_eq!(result, "$USER>");
+    }
+
+    #[test]
+    fn test_escaped_dollar() {
+        let env = make_env(&[("USER", "alice")]);
+        let result = expand_variables(r"\$USER costs \${USER} and \\$USER", &env).unwrap();
+        assert_eq!(result, r"$USER costs ${USER} and \$USER");
+    }
+
+    #[test]
+    fn test_expand_with_lookup() {
+        let result = expand_with("${A}-$B", |name| match name {
+            "A" => Some("first".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(result, "first-");
+    }
 }
@@ -1,7 +1,17 @@
+pub mod diagnostic;
+pub mod document;
 pub mod dotenv;
 pub mod expansion;
 pub mod substitution;
+pub mod writer;
 
-pub use dotenv::DotenvParser;
-pub use expansion::expand_variables;
-pub use substitution::substitute_commands;
+pub use diagnostic::{
+    log_warnings, render_diagnostics, render_violations, Diagnostic, Severity, Violation,
+};
+pub use document::{Document, EntryLine, Line};
+pub use dotenv::{is_valid_key, DotenvParser, Entry, Quote};
+pub use expansion::{expand_variables, expand_with};
+pub use substitution::{
+    substitute_commands, substitute_commands_with, CommandContext, SubstitutionPolicy, WorkingDir,
+};
+pub use writer::{format_entry, format_literal_entry, quote_value, EnvWriter, PUBLIC_KEY_BANNER};
@@ -1,10 +1,98 @@
 use crate::utils::error::{DotenvxError, Result};
-use regex::Regex;
-use std::process::Command;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Directory that substitution commands run in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkingDir {
+    /// The current working directory of dotenvx
+    #[default]
+    Cwd,
+    /// The directory containing the .env file being loaded
+    EnvFile,
+}
+
+/// Controls whether and how `$(...)` and backtick commands are executed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubstitutionPolicy {
+    /// Execute substitutions at all; when disabled, values are kept as literal text
+    pub enabled: bool,
+    /// Executables that may be invoked, matched by file name; `None` allows any
+    pub allowlist: Option<Vec<String>>,
+    /// Time after which a command is killed; `None` waits indefinitely
+    pub timeout: Option<Duration>,
+    /// Maximum number of bytes a command may write to stdout; `None` is unlimited
+    pub max_output: Option<usize>,
+    /// Directory that commands run in
+    pub working_dir: WorkingDir,
+    /// Pass the variables loaded so far to the command's environment
+    pub pass_env: bool,
+}
+
+impl Default for SubstitutionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowlist: None,
+            timeout: Some(Duration::from_secs(30)),
+            max_output: Some(1024 * 1024),
+            working_dir: WorkingDir::Cwd,
+            pass_env: false,
+        }
+    }
+}
+
+/// Per-file state that substitution commands run with, resolved from a [`SubstitutionPolicy`]
+#[derive(Debug, Clone, Default)]
+pub struct CommandContext {
+    /// Directory to run the command in; `None` inherits the current directory
+    pub current_dir: Option<PathBuf>,
+    /// Variables added to the inherited process environment
+    pub env: HashMap<String, String>,
+}
+
+impl CommandContext {
+    /// Resolve the context for commands found in `env_file`
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The substitution policy in effect
+    /// * `env_file` - The .env file being evaluated, if any
+    /// * `loaded` - Variables loaded before the command runs
+    pub fn resolve(
+        policy: &SubstitutionPolicy,
+        env_file: Option<&Path>,
+        loaded: &HashMap<String, String>,
+    ) -> Self {
+        let current_dir = match policy.working_dir {
+            WorkingDir::Cwd => None,
+            WorkingDir::EnvFile => env_file
+                .and_then(Path::parent)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map(Path::to_path_buf),
+        };
+        let env = if policy.pass_env {
+            loaded.clone()
+        } else {
+            HashMap::new()
+        };
+
+        Self { current_dir, env }
+    }
+}
 
 /// Substitute command outputs in a value string
 ///
 /// Supports `$(command)` syntax to execute shell commands and replace with their output.
+/// Commands run under the default [`SubstitutionPolicy`].
 ///
 /// # Arguments
 ///
@@ -23,65 +111,469 @@ use std::process::Command;
 /// println!("{}", result);
 /// ```
 pub fn substitute_commands(value: &str) -> Result<String> {
-    let re = Regex::new(r"\$\(([^)]+)\)")
-        .map_err(|e| DotenvxError::CommandSubstitution(e.to_string()))?;
+    substitute_commands_with(
+        value,
+        &SubstitutionPolicy::default(),
+        &CommandContext::default(),
+    )
+}
+
+/// Substitute command outputs in a value string under a policy
+///
+/// # Arguments
+///
+/// * `value` - The value string containing command substitutions
+/// * `policy` - Limits applied to every command
+/// * `context` - Directory and environment the commands run with
+///
+/// # Returns
+///
+/// The value with commands substituted with their outputs, or unchanged if
+/// substitution is disabled
+pub fn substitute_commands_with(
+    value: &str,
+    policy: &SubstitutionPolicy,
+    context: &CommandContext,
+) -> Result<String> {
+    if !policy.enabled {
+        return Ok(value.to_string());
+    }
+
+    let spans = scan_substitutions(value)?;
 
-    let mut result = value.to_string();
+    let mut result = String::new();
+    let mut last = 0;
+    for span in &spans {
+        result.push_str(&value[last..span.start]);
+        result.push_str(&evaluate(value, span, policy, context)?);
+        last = span.end;
+    }
+    result.push_str(&value[last..]);
 
-    // Collect all matches first to avoid iterator invalidation
-    let matches: Vec<_> = re.captures_iter(value).collect();
+    Ok(result)
+}
 
-    // Process matches in reverse order to maintain correct indices
-    for caps in matches.iter().rev() {
-        let full_match = caps.get(0).unwrap();
-        let command_str = &caps[1];
+/// A `$(...)` substitution found in a value, as byte offsets into the value
+#[derive(Debug, PartialEq, Eq)]
+struct Span {
+    /// Offset of the `$`
+    start: usize,
+    /// Offset just past the closing `)`
+    end: usize,
+    /// Substitutions nested inside this one
+    children: Vec<Span>,
+}
 
-        // Execute the command
-        let output = execute_command(command_str)?;
+fn span_error(value: &str, start: usize, end: usize, message: &str) -> DotenvxError {
+    DotenvxError::CommandSubstitution(format!(
+        "{} in `{}` at {}..{}",
+        message,
+        &value[start..end],
+        start,
+        end
+    ))
+}
 
-        // Replace in result string
-        let start = full_match.start();
-        let end = full_match.end();
-        result.replace_range(start..end, &output);
+fn is_substitution_start(bytes: &[u8], i: usize) -> bool {
+    // `$((` starts arithmetic expansion, which is left alone
+    bytes[i] == b'$' && bytes.get(i + 1) == Some(&b'(') && bytes.get(i + 2) != Some(&b'(')
+}
+
+/// Offset of the first top-level `$(` at or after `from`, skipping `\$`
+fn next_substitution(bytes: &[u8], mut from: usize) -> Option<usize> {
+    while from < bytes.len() {
+        if bytes[from] == b'\\' {
+            from += 2;
+        } else if is_substitution_start(bytes, from) {
+            return Some(from);
+        } else {
+            from += 1;
+        }
     }
+    None
+}
 
-    Ok(result)
+/// Whether a value contains a `$(...)` substitution that is not escaped
+pub(crate) fn has_substitution(value: &str) -> bool {
+    next_substitution(value.as_bytes(), 0).is_some()
+}
+
+/// Find the top-level `$(...)` substitutions in a value
+fn scan_substitutions(value: &str) -> Result<Vec<Span>> {
+    let bytes = value.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while let Some(start) = next_substitution(bytes, i) {
+        let span = scan_command(value, start)?;
+        i = span.end;
+        spans.push(span);
+    }
+
+    Ok(spans)
+}
+
+/// Scan a `$(...)` starting at `start`, honouring quotes, escapes and nesting
+fn scan_command(value: &str, start: usize) -> Result<Span> {
+    let bytes = value.as_bytes();
+    let mut children = Vec::new();
+    let mut depth = 0;
+    let mut i = start + 2;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\'' => i = skip_past(value, start, i, b'\'')?,
+            b'`' => i = skip_past(value, start, i, b'`')?,
+            b'"' => {
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => {
+                            return Err(span_error(value, start, i, "unterminated double quote"))
+                        }
+                        Some(b'\\') => i += 2,
+                        Some(b'"') => break,
+                        Some(b'`') => i = skip_past(value, start, i, b'`')?,
+                        Some(_) if is_substitution_start(bytes, i) => {
+                            let child = scan_command(value, i)?;
+                            i = child.end;
+                            children.push(child);
+                        }
+                        Some(_) => i += 1,
+                    }
+                }
+                i += 1;
+            }
+            _ if is_substitution_start(bytes, i) => {
+                let child = scan_command(value, i)?;
+                i = child.end;
+                children.push(child);
+            }
+            b'(' => {
+                depth += 1;
+                i += 1;
+            }
+            b')' if depth == 0 => {
+                return Ok(Span {
+                    start,
+                    end: i + 1,
+                    children,
+                })
+            }
+            b')' => {
+                depth -= 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    Err(span_error(
+        value,
+        start,
+        value.len(),
+        "unterminated command substitution",
+    ))
+}
+
+/// Return the offset just past the next unescaped `delimiter` after `i`
+fn skip_past(value: &str, start: usize, i: usize, delimiter: u8) -> Result<usize> {
+    let bytes = value.as_bytes();
+    let mut j = i + 1;
+    while j < bytes.len() {
+        if bytes[j] == b'\\' && delimiter != b'\'' {
+            j += 2;
+        } else if bytes[j] == delimiter {
+            return Ok(j + 1);
+        } else {
+            j += 1;
+        }
+    }
+
+    Err(span_error(
+        value,
+        start,
+        value.len(),
+        &format!("unterminated {}", delimiter as char),
+    ))
+}
+
+/// Run a substitution, evaluating nested ones first
+///
+/// The output of a nested substitution is handed to the outer shell through an
+/// environment variable rather than spliced into its source, so it is expanded
+/// exactly like `$(...)` would be and never re-parsed as shell code.
+fn evaluate(
+    value: &str,
+    span: &Span,
+    policy: &SubstitutionPolicy,
+    context: &CommandContext,
+) -> Result<String> {
+    let mut command = String::new();
+    let mut nested = context.clone();
+    let mut last = span.start + 2;
+
+    for (n, child) in span.children.iter().enumerate() {
+        let output = evaluate(value, child, policy, context)?;
+        let name = format!("__DOTENVX_SUBSTITUTION_{}", n);
+
+        command.push_str(&value[last..child.start]);
+        if cfg!(target_os = "windows") {
+            command.push_str(&format!("%{}%", name));
+        } else {
+            command.push_str(&format!("${{{}}}", name));
+        }
+        nested.env.insert(name, output);
+        last = child.end;
+    }
+    command.push_str(&value[last..span.end - 1]);
+
+    execute_command(&command, policy, &nested).map_err(|e| match e {
+        DotenvxError::CommandSubstitution(message) => {
+            span_error(value, span.start, span.end, &message)
+        }
+        other => other,
+    })
+}
+
+/// Check that every command in a shell snippet invokes an allowed executable
+///
+/// The snippet is split on unquoted `|`, `;`, `&`, parentheses and newlines, and
+/// the first word of each part (after any `NAME=value` assignments) must match
+/// an allowlist entry by file name. Backticks are rejected, since the
+/// executables they run cannot be checked here, and so are unquoted `<` and
+/// `>`, since redirections, here-strings and process substitutions read or
+/// write files no matter which executable runs.
+fn check_allowlist(command_str: &str, allowlist: &[String]) -> Result<()> {
+    if command_str.contains('`') {
+        return Err(DotenvxError::CommandSubstitution(format!(
+            "backticks are not allowed with an allowlist: {}",
+            command_str
+        )));
+    }
+
+    let mut segments = vec![String::new()];
+    let mut quote: Option<char> = None;
+    let mut chars = command_str.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (None, '\\') => {
+                segments.last_mut().unwrap().push(ch);
+                if let Some(next) = chars.next() {
+                    segments.last_mut().unwrap().push(next);
+                }
+            }
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                segments.last_mut().unwrap().push(ch);
+            }
+            (Some(q), _) if ch == q => {
+                quote = None;
+                segments.last_mut().unwrap().push(ch);
+            }
+            (None, '<' | '>') => {
+                return Err(DotenvxError::CommandSubstitution(format!(
+                    "redirections are not allowed with an allowlist: {}",
+                    command_str
+                )));
+            }
+            (None, '|' | ';' | '&' | '(' | ')' | '\n') => segments.push(String::new()),
+            _ => segments.last_mut().unwrap().push(ch),
+        }
+    }
+
+    for segment in &segments {
+        let Some(program) = segment
+            .split_whitespace()
+            .find(|word| !is_assignment(word))
+            .map(|word| word.trim_matches(|c| c == '\'' || c == '"'))
+        else {
+            continue;
+        };
+
+        let name = Path::new(program)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !allowlist.contains(&name) {
+            return Err(DotenvxError::CommandSubstitution(format!(
+                "'{}' is not in the substitution allowlist",
+                program
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+    })
 }
 
 /// Execute a shell command and return its output
-fn execute_command(command_str: &str) -> Result<String> {
-    let output = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(["/C", command_str])
-            .output()
-            .map_err(|e| {
-                DotenvxError::CommandSubstitution(format!("Failed to execute command: {}", e))
-            })?
+fn execute_command(
+    command_str: &str,
+    policy: &SubstitutionPolicy,
+    context: &CommandContext,
+) -> Result<String> {
+    if let Some(allowlist) = &policy.allowlist {
+        check_allowlist(command_str, allowlist)?;
+    }
+
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", command_str]);
+        command
     } else {
-        Command::new("sh")
-            .args(["-c", command_str])
-            .output()
-            .map_err(|e| {
-                DotenvxError::CommandSubstitution(format!("Failed to execute command: {}", e))
-            })?
+        let mut command = Command::new("sh");
+        command.args(["-c", command_str]);
+        command
     };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .envs(&context.env);
+    if let Some(dir) = &context.current_dir {
+        command.current_dir(dir);
+    }
+
+    // Run in its own process group so a timeout also kills any grandchildren
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let mut child = command.spawn().map_err(|e| {
+        DotenvxError::CommandSubstitution(format!("Failed to execute command: {}", e))
+    })?;
+
+    // Read stdout one byte past the limit so an overflow can be detected
+    let limit = policy.max_output.map(|max| max as u64 + 1);
+    let stdout = read_pipe(child.stdout.take().unwrap(), limit);
+    let stderr = read_pipe_keeping(child.stderr.take().unwrap(), 64 * 1024);
+
+    let deadline = policy.timeout.map(|timeout| Instant::now() + timeout);
+    let mut stdout_buf = None;
+    let status = loop {
+        if stdout_buf.is_none() {
+            if let Ok(buf) = stdout.try_recv() {
+                stdout_buf = Some(buf);
+            }
+        }
+        if let (Some(max), Some(buf)) = (policy.max_output, &stdout_buf) {
+            if buf.len() > max {
+                kill_command(&mut child);
+                return Err(DotenvxError::CommandSubstitution(format!(
+                    "Command output exceeded {} bytes: {}",
+                    max, command_str
+                )));
+            }
+        }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            kill_command(&mut child);
+            return Err(DotenvxError::CommandSubstitution(format!(
+                "Command timed out after {}s: {}",
+                policy.timeout.unwrap_or_default().as_secs_f64(),
+                command_str
+            )));
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    // Background processes may still hold the pipe open after the shell exits
+    let stdout_buf = match (stdout_buf, deadline) {
+        (Some(buf), _) => buf,
+        (None, None) => stdout.recv().unwrap_or_default(),
+        (None, Some(deadline)) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match stdout.recv_timeout(remaining) {
+                Ok(buf) => buf,
+                Err(_) => {
+                    kill_command(&mut child);
+                    return Err(DotenvxError::CommandSubstitution(format!(
+                        "Command timed out after {}s: {}",
+                        policy.timeout.unwrap_or_default().as_secs_f64(),
+                        command_str
+                    )));
+                }
+            }
+        }
+    };
+    if policy.max_output.is_some_and(|max| stdout_buf.len() > max) {
+        return Err(DotenvxError::CommandSubstitution(format!(
+            "Command output exceeded {} bytes: {}",
+            policy.max_output.unwrap_or_default(),
+            command_str
+        )));
+    }
+
+    if !status.success() {
+        let stderr_buf = stderr.recv().unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr_buf);
         return Err(DotenvxError::CommandSubstitution(format!(
             "Command failed with exit code {}: {}",
-            output.status.code().unwrap_or(-1),
+            status.code().unwrap_or(-1),
             stderr
         )));
     }
 
-    let stdout = String::from_utf8(output.stdout)
+    let stdout = String::from_utf8(stdout_buf)
         .map_err(|e| DotenvxError::CommandSubstitution(format!("Invalid UTF-8 output: {}", e)))?;
 
     // Trim trailing newline
     Ok(stdout.trim_end().to_string())
 }
 
+/// Read a pipe to the end (or up to `limit` bytes) on a background thread
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R, limit: Option<u64>) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = match limit {
+            Some(limit) => (&mut pipe).take(limit).read_to_end(&mut buf),
+            None => pipe.read_to_end(&mut buf),
+        };
+        let _ = tx.send(buf);
+    });
+    rx
+}
+
+/// Read a pipe to the end on a background thread, keeping only the first
+/// `keep` bytes
+///
+/// Unlike stopping at a limit, this never closes the pipe early, so a command
+/// writing more than `keep` bytes is not killed by `SIGPIPE`.
+fn read_pipe_keeping<R: Read + Send + 'static>(mut pipe: R, keep: u64) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = (&mut pipe).take(keep).read_to_end(&mut buf);
+        let _ = std::io::copy(&mut pipe, &mut std::io::sink());
+        let _ = tx.send(buf);
+    });
+    rx
+}
+
+/// Kill a substitution command and everything it started
+fn kill_command(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+        let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,6 +594,11 @@ mod tests {
     fn test_no_substitution() {
         let result = substitute_commands("plain text").unwrap();
         assert_eq!(result, "plain text");
+
+        // `\$(` is escaped and kept for expansion to unescape
+        let result = substitute_commands("cost \\$(whoami)").unwrap();
+        assert_eq!(result, "cost \\$(whoami)");
+        assert!(!has_substitution("cost \\$(whoami)"));
     }
 
     #[test]
@@ -122,4 +619,193 @@ mod tests {
         let result = substitute_commands("$(nonexistent_command_xyz)");
         assert!(result.is_err());
     }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_nested_substitution() {
+        let result = substitute_commands("[$(echo $(echo inner) outer)]").unwrap();
+        assert_eq!(result, "[inner outer]");
+
+        let result = substitute_commands("$(echo \"$(printf '%s' 'a  b')\")").unwrap();
+        assert_eq!(result, "a  b");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_quoted_parentheses() {
+        let result = substitute_commands("$(printf '%s' \")\")").unwrap();
+        assert_eq!(result, ")");
+
+        let result = substitute_commands("$(echo 'a)b' | tr ')' x)").unwrap();
+        assert_eq!(result, "axb");
+
+        let result = substitute_commands("$(echo \\) \"(x)\")").unwrap();
+        assert_eq!(result, ") (x)");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_nested_output_is_not_code() {
+        let result = substitute_commands("$(echo $(printf '%s' '$(echo pwned)'))").unwrap();
+        assert_eq!(result, "$(echo pwned)");
+    }
+
+    #[test]
+    fn test_scan_spans() {
+        let spans = scan_substitutions("a $(x $(y)) $(z)").unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].start, spans[0].end), (2, 11));
+        assert_eq!(
+            (spans[0].children[0].start, spans[0].children[0].end),
+            (6, 10)
+        );
+        assert_eq!((spans[1].start, spans[1].end), (12, 16));
+
+        // Arithmetic expansion is not a command substitution
+        assert!(scan_substitutions("$((1 + 2))").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_substitution() {
+        let err = substitute_commands("ok $(echo 'x)")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unterminated"));
+        assert!(err.contains("at 3..13"));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_error_reports_original_span() {
+        let err = substitute_commands("ab $(echo $(exit 3))")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`$(exit 3)` at 10..19"), "{}", err);
+    }
+
+    #[test]
+    fn test_disabled_substitution() {
+        let policy = SubstitutionPolicy {
+            enabled: false,
+            ..SubstitutionPolicy::default()
+        };
+        let result =
+            substitute_commands_with("$(echo hi)", &policy, &CommandContext::default()).unwrap();
+        assert_eq!(result, "$(echo hi)");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_allowlist() {
+        let policy = SubstitutionPolicy {
+            allowlist: Some(vec!["echo".to_string(), "tr".to_string()]),
+            ..SubstitutionPolicy::default()
+        };
+        let context = CommandContext::default();
+
+        let result = substitute_commands_with("$(echo abc | tr a x)", &policy, &context).unwrap();
+        assert_eq!(result, "xbc");
+
+        let result = substitute_commands_with("$(echo a; whoami)", &policy, &context);
+        assert!(result.is_err());
+        let result = substitute_commands_with("$(LANG=C /usr/bin/id)", &policy, &context);
+        assert!(result.is_err());
+        let result = substitute_commands_with("$(echo ';' 'rm')", &policy, &context).unwrap();
+        assert_eq!(result, "; rm");
+
+        for command in [
+            "$(echo x > out)",
+            "$(echo x>>out)",
+            "$(tr a b < /etc/passwd)",
+            "$(tr a b <<< secret)",
+            "$(echo <(id))",
+            "$(echo `id`)",
+        ] {
+            assert!(
+                substitute_commands_with(command, &policy, &context).is_err(),
+                "{}",
+                command
+            );
+        }
+        let result = substitute_commands_with("$(echo '<a>' \"b>c\")", &policy, &context).unwrap();
+        assert_eq!(result, "<a> b>c");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_timeout_kills_command() {
+        let policy = SubstitutionPolicy {
+            timeout: Some(Duration::from_millis(200)),
+            ..SubstitutionPolicy::default()
+        };
+        let started = Instant::now();
+        let result =
+            substitute_commands_with("$(sleep 5 | cat)", &policy, &CommandContext::default());
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_max_output() {
+        let policy = SubstitutionPolicy {
+            max_output: Some(4),
+            ..SubstitutionPolicy::default()
+        };
+        let context = CommandContext::default();
+        assert!(substitute_commands_with("$(echo 123456)", &policy, &context).is_err());
+        assert!(substitute_commands_with("$(yes)", &policy, &context).is_err());
+        assert_eq!(
+            substitute_commands_with("$(echo 123)", &policy, &context).unwrap(),
+            "123"
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_large_stderr() {
+        let context = CommandContext::default();
+        let policy = SubstitutionPolicy::default();
+        let result = substitute_commands_with(
+            "$(head -c 1000000 /dev/zero >&2 && echo ok)",
+            &policy,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(result, "ok");
+
+        // Only the start of stderr is kept for the error message
+        let error = substitute_commands_with(
+            "$(head -c 1000000 /dev/zero | tr '\\0' x >&2; exit 3)",
+            &policy,
+            &context,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("exit code 3"), "{}", &error[..100]);
+        assert!(error.len() < 70 * 1024);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_context() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let loaded: HashMap<String, String> = [("LOADED".to_string(), "yes".to_string())].into();
+
+        let policy = SubstitutionPolicy {
+            working_dir: WorkingDir::EnvFile,
+            pass_env: true,
+            ..SubstitutionPolicy::default()
+        };
+        let context = CommandContext::resolve(&policy, Some(&env_file), &loaded);
+        let result = substitute_commands_with("$(pwd)/$(echo $LOADED)", &policy, &context).unwrap();
+        let expected = temp.path().canonicalize().unwrap();
+        assert_eq!(result, format!("{}/yes", expected.display()));
+
+        let context =
+            CommandContext::resolve(&SubstitutionPolicy::default(), Some(&env_file), &loaded);
+        assert!(context.current_dir.is_none());
+        assert!(context.env.is_empty());
+    }
 }
@@ -0,0 +1,179 @@
+use crate::parser::dotenv::{is_valid_key, DotenvParser};
+use crate::utils::error::{DotenvxError, Result};
+use std::io::Write;
+
+/// Comment block written above `DOTENV_PUBLIC_KEY`
+pub const PUBLIC_KEY_BANNER: [&str; 4] = [
+    "#/-------------------[DOTENV_PUBLIC_KEY]--------------------/",
+    "#/            public-key encryption for .env files          /",
+    "#/       [how it works](https://dotenvx.com/encryption)     /",
+    "#/----------------------------------------------------------/",
+];
+
+/// Quote a value for a .env file
+///
+/// Values are double-quoted and escaped, so `$VAR` references in them are
+/// expanded when the file is loaded. With `literal`, [`DotenvParser`] reads the
+/// value back unchanged instead: a value containing `$` is single-quoted, or,
+/// if it also spans lines, double-quoted with each `$` escaped as `\$`.
+pub fn quote_value(value: &str, literal: bool) -> String {
+    if !literal || !value.contains('$') {
+        return format!("\"{}\"", DotenvParser::escape(value));
+    }
+    if value.contains(['\n', '\r']) {
+        return format!("\"{}\"", DotenvParser::escape(value).replace('$', "\\$"));
+    }
+    format!("'{}'", value)
+}
+
+/// Format a `KEY="value"` line, without a line terminator
+///
+/// References to other variables in `value` are expanded when the file is
+/// loaded; use [`format_literal_entry`] for a value that must be read back
+/// unchanged.
+///
+/// # Arguments
+///
+/// * `key` - Variable name
+/// * `value` - Value to write, as it would appear between double quotes
+///
+/// # Returns
+///
+/// The formatted assignment
+pub fn format_entry(key: &str, value: &str) -> String {
+    format!("{}={}", key, quote_value(value, false))
+}
+
+/// Format a `KEY=value` line that loads as exactly `value`
+///
+/// # Arguments
+///
+/// * `key` - Variable name
+/// * `value` - Value to write, as it should be read back
+///
+/// # Returns
+///
+/// The formatted assignment
+pub fn format_literal_entry(key: &str, value: &str) -> String {
+    format!("{}={}", key, quote_value(value, true))
+}
+
+/// Writes .env content line by line with correct quoting
+///
+/// ```rust
+/// use dotenvx::parser::EnvWriter;
+///
+/// let mut writer = EnvWriter::new(Vec::new());
+/// writer.comment("Database").unwrap();
+/// writer.entry("DB_PASSWORD", "p\"ss word").unwrap();
+/// let content = String::from_utf8(writer.into_inner()).unwrap();
+/// assert_eq!(content, "# Database\nDB_PASSWORD=\"p\\\"ss word\"\n");
+/// ```
+#[derive(Debug)]
+pub struct EnvWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> EnvWriter<W> {
+    /// Create a writer over `inner`
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Write a `KEY=value` assignment that loads as exactly `value`,
+    /// rejecting invalid variable names
+    pub fn entry(&mut self, key: &str, value: &str) -> Result<()> {
+        if !is_valid_key(key) {
+            return Err(DotenvxError::InvalidValue {
+                key: key.to_string(),
+                message: "not a valid variable name".to_string(),
+            });
+        }
+        writeln!(self.inner, "{}", format_literal_entry(key, value))?;
+        Ok(())
+    }
+
+    /// Write a comment, one `#` line per line of `text`
+    pub fn comment(&mut self, text: &str) -> Result<()> {
+        for line in text.lines() {
+            writeln!(self.inner, "# {}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Write an empty line
+    pub fn blank_line(&mut self) -> Result<()> {
+        writeln!(self.inner)?;
+        Ok(())
+    }
+
+    /// Write the public key banner, `DOTENV_PUBLIC_KEY` and an empty line
+    pub fn public_key(&mut self, public_key: &str) -> Result<()> {
+        for line in PUBLIC_KEY_BANNER {
+            writeln!(self.inner, "{}", line)?;
+        }
+        self.entry("DOTENV_PUBLIC_KEY", public_key)?;
+        self.blank_line()
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_entry_roundtrip() {
+        let values = [
+            "",
+            "plain",
+            "with \"quotes\" and 'apostrophes'",
+            "C:\\path\\to",
+            "multi\nline\r\n\ttext",
+            "$HOME/literal",
+            "it's $5",
+            "$(whoami)",
+            "$A\nB",
+            "line\n$(whoami) \\$HOME",
+            "  padded  ",
+            "# not a comment",
+        ];
+        for value in values {
+            let line = format_literal_entry("KEY", value);
+            let mut parser = DotenvParser::new();
+            parser.parse_with_processing(&line).unwrap();
+            assert_eq!(parser.variables().get("KEY").unwrap(), value, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_format_entry_expands() {
+        assert_eq!(format_entry("URL", "${HOST}/api"), "URL=\"${HOST}/api\"");
+        assert_eq!(
+            format_literal_entry("URL", "${HOST}/api"),
+            "URL='${HOST}/api'"
+        );
+
+        let mut parser = DotenvParser::new();
+        let content = format!("HOST=db\n{}", format_entry("URL", "${HOST}/api"));
+        parser.parse_with_processing(&content).unwrap();
+        assert_eq!(parser.variables().get("URL").unwrap(), "db/api");
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut writer = EnvWriter::new(Vec::new());
+        writer.public_key("02ab").unwrap();
+        writer.comment("first\nsecond").unwrap();
+        writer.entry("A", "1").unwrap();
+        assert!(writer.entry("BAD KEY", "1").is_err());
+
+        let content = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(content.starts_with(PUBLIC_KEY_BANNER[0]));
+        assert!(content.ends_with("DOTENV_PUBLIC_KEY=\"02ab\"\n\n# first\n# second\nA=\"1\"\n"));
+    }
+}
@@ -0,0 +1,730 @@
+//! Serialize Rust values into .env files
+//!
+//! The mapping mirrors [`crate::de`]: struct fields become upper-cased
+//! variables, nested structs are prefixed with the field name and `__`,
+//! sequences are comma-separated, durations are written as `90s` or `250ms`,
+//! and `None` fields are left out.
+//!
+//! ```rust
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     port: u16,
+//!     hosts: Vec<String>,
+//!     db: Database,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Database {
+//!     password: String,
+//! }
+//!
+//! let config = Config {
+//!     port: 8080,
+//!     hosts: vec!["a.com".into(), "b.com".into()],
+//!     db: Database { password: "p@ss".into() },
+//! };
+//! assert_eq!(
+//!     dotenvx::to_string(&config).unwrap(),
+//!     "PORT=\"8080\"\nHOSTS=\"a.com,b.com\"\nDB__PASSWORD=\"p@ss\"\n"
+//! );
+//! ```
+
+use crate::crypto::encrypt;
+use crate::de::NESTING_SEPARATOR;
+use crate::parser::EnvWriter;
+use crate::utils::error::{DotenvxError, Result};
+use glob::Pattern;
+use serde::ser::{self, Impossible, Serialize};
+use std::io::Write;
+
+impl ser::Error for DotenvxError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        DotenvxError::Serialize(msg.to_string())
+    }
+}
+
+/// Serialize a value into .env content
+///
+/// # Arguments
+///
+/// * `value` - A struct or map to serialize
+///
+/// # Returns
+///
+/// The .env content
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let mut output = Vec::new();
+    to_writer(&mut output, value)?;
+    Ok(String::from_utf8(output).expect("serialized .env content is UTF-8"))
+}
+
+/// Serialize a value into .env content, encrypting the selected variables
+///
+/// The output starts with `DOTENV_PUBLIC_KEY`, so the file can be decrypted
+/// by `dotenvx` with the matching private key.
+///
+/// # Arguments
+///
+/// * `value` - A struct or map to serialize
+/// * `public_key` - Hex public key to encrypt with
+/// * `keys` - Variable names or glob patterns to encrypt, e.g. `DB__PASSWORD` or `*_TOKEN`
+///
+/// # Returns
+///
+/// The .env content
+pub fn to_string_encrypted<T: Serialize + ?Sized>(
+    value: &T,
+    public_key: &str,
+    keys: &[&str],
+) -> Result<String> {
+    let patterns = keys
+        .iter()
+        .map(|key| Pattern::new(key).map_err(|e| DotenvxError::Other(e.to_string())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut writer = EnvWriter::new(Vec::new());
+    writer.public_key(public_key)?;
+    for (key, value) in collect(value)? {
+        if patterns.iter().any(|pattern| pattern.matches(&key)) {
+            writer.entry(&key, &encrypt(&value, public_key)?)?;
+        } else {
+            writer.entry(&key, &value)?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()).expect("serialized .env content is UTF-8"))
+}
+
+/// Serialize a value as .env content into `writer`
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    let mut writer = EnvWriter::new(writer);
+    for (key, value) in collect(value)? {
+        writer.entry(&key, &value)?;
+    }
+    Ok(())
+}
+
+fn collect<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    value.serialize(EnvSerializer {
+        entries: &mut entries,
+        key: String::new(),
+        root: true,
+    })?;
+    Ok(entries)
+}
+
+/// Serializes a value into variables, at the root or under a variable name
+struct EnvSerializer<'a> {
+    entries: &'a mut Vec<(String, String)>,
+    key: String,
+    root: bool,
+}
+
+impl<'a> EnvSerializer<'a> {
+    fn scalar(self, value: String) -> Result<()> {
+        if self.root {
+            return Err(DotenvxError::Serialize(
+                "only structs and maps can be serialized as .env files".to_string(),
+            ));
+        }
+        self.entries.push((self.key, value));
+        Ok(())
+    }
+
+    fn prefix(&self) -> String {
+        if self.root {
+            String::new()
+        } else {
+            format!("{}{}", self.key, NESTING_SEPARATOR)
+        }
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<Self::Ok> {
+                self.scalar(value.to_string())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for EnvSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = Impossible<(), DotenvxError>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), DotenvxError>;
+
+    serialize_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<()> {
+        Err(DotenvxError::Serialize(format!(
+            "{}: byte arrays are not supported",
+            self.key
+        )))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.scalar(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(DotenvxError::Serialize(format!(
+            "{}: enum variant '{}' carries data, which is not supported",
+            self.key, variant
+        )))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a>> {
+        if self.root {
+            return Err(DotenvxError::Serialize(
+                "only structs and maps can be serialized as .env files".to_string(),
+            ));
+        }
+        Ok(SeqSerializer {
+            entries: self.entries,
+            key: self.key,
+            items: Vec::new(),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(DotenvxError::Serialize(format!(
+            "{}: enum variant '{}' carries data, which is not supported",
+            self.key, variant
+        )))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>> {
+        let prefix = self.prefix();
+        Ok(MapSerializer {
+            entries: self.entries,
+            prefix,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<StructSerializer<'a>> {
+        // std::time::Duration serializes as a struct of seconds and nanoseconds
+        if name == "Duration" && !self.root {
+            return Ok(StructSerializer::Duration {
+                entries: self.entries,
+                key: self.key,
+                secs: 0,
+                nanos: 0,
+            });
+        }
+        let prefix = self.prefix();
+        Ok(StructSerializer::Fields {
+            entries: self.entries,
+            prefix,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(DotenvxError::Serialize(format!(
+            "{}: enum variant '{}' carries data, which is not supported",
+            self.key, variant
+        )))
+    }
+}
+
+enum StructSerializer<'a> {
+    Fields {
+        entries: &'a mut Vec<(String, String)>,
+        prefix: String,
+    },
+    Duration {
+        entries: &'a mut Vec<(String, String)>,
+        key: String,
+        secs: u64,
+        nanos: u32,
+    },
+}
+
+impl<'a> ser::SerializeStruct for StructSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        field: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        match self {
+            Self::Fields { entries, prefix } => value.serialize(EnvSerializer {
+                entries,
+                key: format!("{}{}", prefix, field.to_uppercase()),
+                root: false,
+            }),
+            Self::Duration { secs, nanos, .. } => {
+                let value = value.serialize(ValueSerializer)?;
+                let parsed = match field {
+                    "secs" => value.parse().map(|v| *secs = v).is_ok(),
+                    "nanos" => value.parse().map(|v| *nanos = v).is_ok(),
+                    _ => true,
+                };
+                if parsed {
+                    Ok(())
+                } else {
+                    Err(DotenvxError::Serialize(format!(
+                        "invalid duration {}",
+                        field
+                    )))
+                }
+            }
+        }
+    }
+
+    fn end(self) -> Result<()> {
+        if let Self::Duration {
+            entries,
+            key,
+            secs,
+            nanos,
+        } = self
+        {
+            entries.push((key, format_duration(secs, nanos)));
+        }
+        Ok(())
+    }
+}
+
+/// Format a duration in the largest unit that represents it exactly
+fn format_duration(secs: u64, nanos: u32) -> String {
+    if nanos == 0 {
+        return format!("{}s", secs);
+    }
+    // Whole milliseconds, unless there are too many to count in a u64
+    let (whole_ms, sub_ms) = (nanos / 1_000_000, nanos % 1_000_000);
+    let millis = secs
+        .checked_mul(1000)
+        .and_then(|ms| ms.checked_add(u64::from(whole_ms)));
+    match millis {
+        Some(millis) if sub_ms == 0 => format!("{}ms", millis),
+        _ => {
+            let fraction = format!("{:09}", nanos);
+            format!("{}.{}s", secs, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+struct SeqSerializer<'a> {
+    entries: &'a mut Vec<(String, String)>,
+    key: String,
+    items: Vec<String>,
+}
+
+impl<'a> SeqSerializer<'a> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let item = value.serialize(ValueSerializer)?;
+        if item.contains(',') {
+            return Err(DotenvxError::Serialize(format!(
+                "{}: list item {:?} contains a comma",
+                self.key, item
+            )));
+        }
+        self.items.push(item);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        self.entries.push((self.key, self.items.join(",")));
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeSeq for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a> {
+    entries: &'a mut Vec<(String, String)>,
+    prefix: String,
+    key: Option<String>,
+}
+
+impl<'a> ser::SerializeMap for MapSerializer<'a> {
+    type Ok = ();
+    type Error = DotenvxError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(format!(
+            "{}{}",
+            self.prefix,
+            key.serialize(ValueSerializer)?
+        ));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| DotenvxError::Serialize("map value without a key".to_string()))?;
+        value.serialize(EnvSerializer {
+            entries: self.entries,
+            key,
+            root: false,
+        })
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes a scalar into its text form
+struct ValueSerializer;
+
+macro_rules! value_display {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<String> {
+                Ok(value.to_string())
+            }
+        )*
+    };
+}
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    Err(DotenvxError::Serialize(format!(
+        "{} cannot be used as a list item or map key",
+        what
+    )))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = String;
+    type Error = DotenvxError;
+    type SerializeSeq = Impossible<String, DotenvxError>;
+    type SerializeTuple = Impossible<String, DotenvxError>;
+    type SerializeTupleStruct = Impossible<String, DotenvxError>;
+    type SerializeTupleVariant = Impossible<String, DotenvxError>;
+    type SerializeMap = Impossible<String, DotenvxError>;
+    type SerializeStruct = Impossible<String, DotenvxError>;
+    type SerializeStructVariant = Impossible<String, DotenvxError>;
+
+    value_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String> {
+        unsupported("a byte array")
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        unsupported("an enum variant with data")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        unsupported("a list")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        unsupported("a tuple")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported("a tuple struct")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported("an enum variant with data")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        unsupported("a map")
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        unsupported("a struct")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported("an enum variant with data")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        port: u16,
+        debug: bool,
+        timeout: Duration,
+        poll: Duration,
+        hosts: Vec<String>,
+        db: Database,
+        cache: Option<Database>,
+        mode: Mode,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Database {
+        password: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Production,
+    }
+
+    fn config() -> Config {
+        Config {
+            name: "say \"hi\" $HOME".to_string(),
+            port: 8080,
+            debug: true,
+            timeout: Duration::from_secs(90),
+            poll: Duration::from_millis(250),
+            hosts: vec!["a.com".to_string(), "b.com".to_string()],
+            db: Database {
+                password: "p\"ss\nword".to_string(),
+            },
+            cache: None,
+            mode: Mode::Production,
+        }
+    }
+
+    #[test]
+    fn test_to_string() {
+        let content = to_string(&config()).unwrap();
+        assert_eq!(
+            content,
+            "NAME='say \"hi\" $HOME'\nPORT=\"8080\"\nDEBUG=\"true\"\nTIMEOUT=\"90s\"\n\
+             POLL=\"250ms\"\nHOSTS=\"a.com,b.com\"\nDB__PASSWORD=\"p\\\"ss\\nword\"\nMODE=\"production\"\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let content = to_string(&config()).unwrap();
+        let config_back: Config = crate::from_str(&content).unwrap();
+        assert_eq!(config_back, config());
+    }
+
+    #[test]
+    fn test_to_string_encrypted() {
+        let keypair = crate::crypto::Keypair::generate();
+        let content = to_string_encrypted(&config(), &keypair.public_key(), &["DB__*"]).unwrap();
+        assert!(content.contains("DOTENV_PUBLIC_KEY="));
+        assert!(content.contains("DB__PASSWORD=\"encrypted:"));
+        assert!(content.contains("PORT=\"8080\""));
+
+        let config_back: Config =
+            crate::from_str_with_key(&content, &keypair.private_key()).unwrap();
+        assert_eq!(config_back, config());
+    }
+
+    #[test]
+    fn test_map_and_errors() {
+        let map: BTreeMap<&str, &str> = [("A", "1"), ("B", "x")].into();
+        assert_eq!(to_string(&map).unwrap(), "A=\"1\"\nB=\"x\"\n");
+
+        assert!(to_string(&42).is_err());
+        assert!(to_string(&BTreeMap::from([("LIST", vec!["a,b"])])).is_err());
+        assert!(to_string(&BTreeMap::from([("bad key", "1")])).is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0, 0), "0s");
+        assert_eq!(format_duration(1, 500_000_000), "1500ms");
+        assert_eq!(format_duration(1, 1_500), "1.0000015s");
+        assert_eq!(
+            format_duration(u64::MAX, 5_000_000),
+            "18446744073709551615.005s"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use crate::utils::error::{DotenvxError, Result};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+/// Environment name used when a convention is chosen without one
+pub const DEFAULT_ENV_NAME: &str = "development";
+
+/// Naming scheme for per-environment .env files
+///
+/// Every convention loads `.env`, `.env.local`, `.env.<name>` and
+/// `.env.<name>.local`; they differ in whether `.env.local` or `.env.<name>`
+/// wins. `.env.local` is skipped when the environment is `test`, so test runs
+/// are reproducible across machines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Convention {
+    /// Next.js: `.env.<name>.local` > `.env.local` > `.env.<name>` > `.env`
+    #[default]
+    Nextjs,
+    /// dotenv-rails, with the same precedence as Next.js
+    Rails,
+    /// dotenv-flow: `.env.<name>.local` > `.env.<name>` > `.env.local` > `.env`
+    Flow,
+}
+
+/// Resolve the cascade of .env files for an environment
+///
+/// # Arguments
+///
+/// * `convention` - Naming scheme and precedence
+/// * `env_name` - Environment name, such as `production`
+/// * `dir` - Directory holding the files
+///
+/// # Returns
+///
+/// The files that exist, in load order so that later files win. If none
+/// exist, `.env` alone, so callers report it as missing.
+pub fn resolve_cascade(convention: Convention, env_name: &str, dir: &Path) -> Result<Vec<PathBuf>> {
+    let files: Vec<PathBuf> = cascade_files(convention, env_name, dir)?
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+    if files.is_empty() {
+        return Ok(vec![dir.join(".env")]);
+    }
+    Ok(files)
+}
+
+/// Every file of the cascade for an environment, in load order, whether or
+/// not it exists
+///
+/// # Arguments
+///
+/// * `convention` - Naming scheme and precedence
+/// * `env_name` - Environment name, such as `production`
+/// * `dir` - Directory holding the files
+pub fn cascade_files(convention: Convention, env_name: &str, dir: &Path) -> Result<Vec<PathBuf>> {
+    let valid = !env_name.is_empty()
+        && env_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        return Err(DotenvxError::Other(format!(
+            "invalid environment name '{}': use letters, digits, '-' and '_'",
+            env_name
+        )));
+    }
+
+    let env = format!(".env.{}", env_name);
+    let env_local = format!(".env.{}.local", env_name);
+    let local = (env_name != "test").then_some(".env.local");
+
+    let names: Vec<&str> = match convention {
+        Convention::Nextjs | Convention::Rails => [Some(".env"), Some(env.as_str()), local]
+            .into_iter()
+            .flatten()
+            .chain([env_local.as_str()])
+            .collect(),
+        Convention::Flow => [Some(".env"), local, Some(env.as_str())]
+            .into_iter()
+            .flatten()
+            .chain([env_local.as_str()])
+            .collect(),
+    };
+
+    Ok(names.into_iter().map(|name| dir.join(name)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::fs::write_file;
+    use tempfile::TempDir;
+
+    fn names(files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn project() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        for name in [
+            ".env",
+            ".env.local",
+            ".env.production",
+            ".env.production.local",
+            ".env.test",
+        ] {
+            write_file(&temp.path().join(name), "").unwrap();
+        }
+        temp
+    }
+
+    #[test]
+    fn test_precedence() {
+        let temp = project();
+        let nextjs = resolve_cascade(Convention::Nextjs, "production", temp.path()).unwrap();
+        assert_eq!(
+            names(&nextjs),
+            [
+                ".env",
+                ".env.production",
+                ".env.local",
+                ".env.production.local"
+            ]
+        );
+
+        let flow = resolve_cascade(Convention::Flow, "production", temp.path()).unwrap();
+        assert_eq!(
+            names(&flow),
+            [
+                ".env",
+                ".env.local",
+                ".env.production",
+                ".env.production.local"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skips_local_in_test() {
+        let temp = project();
+        let files = resolve_cascade(Convention::Rails, "test", temp.path()).unwrap();
+        assert_eq!(names(&files), [".env", ".env.test"]);
+    }
+
+    #[test]
+    fn test_missing_and_invalid() {
+        let temp = TempDir::new().unwrap();
+        let files = resolve_cascade(Convention::Nextjs, "staging", temp.path()).unwrap();
+        assert_eq!(files, vec![temp.path().join(".env")]);
+
+        assert!(resolve_cascade(Convention::Nextjs, "../etc", temp.path()).is_err());
+        assert!(resolve_cascade(Convention::Nextjs, "", temp.path()).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::parser::{SubstitutionPolicy, WorkingDir};
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Settings read from a dotenvx config file
+///
+/// ```toml
+/// [substitution]
+/// enabled = true
+/// allow = ["git", "date"]
+/// timeout = 5
+/// max-output = 65536
+/// cwd = "env-file"
+/// pass-env = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Command substitution policy
+    #[serde(default)]
+    pub substitution: SubstitutionConfig,
+}
+
+impl Config {
+    /// Read a config file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the TOML file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&read_file(path)?).map_err(|e| {
+            DotenvxError::Other(format!("invalid config file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Parse config file content
+    pub fn parse(content: &str) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+}
+
+/// Command substitution settings, each unset unless given
+///
+/// Layers are combined with [`SubstitutionConfig::or`], and anything still
+/// unset takes its value from [`SubstitutionPolicy::default`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SubstitutionConfig {
+    /// Execute substitutions at all
+    pub enabled: Option<bool>,
+    /// Executables that commands may invoke
+    pub allow: Option<Vec<String>>,
+    /// Seconds before a command is killed, 0 waiting indefinitely
+    pub timeout: Option<u64>,
+    /// Bytes a command may write to stdout, 0 being unlimited
+    pub max_output: Option<usize>,
+    /// Directory that commands run in
+    pub cwd: Option<WorkingDir>,
+    /// Pass the variables loaded so far to commands
+    pub pass_env: Option<bool>,
+}
+
+impl SubstitutionConfig {
+    /// These settings, with any unset ones taken from `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            enabled: self.enabled.or(fallback.enabled),
+            allow: self.allow.or(fallback.allow),
+            timeout: self.timeout.or(fallback.timeout),
+            max_output: self.max_output.or(fallback.max_output),
+            cwd: self.cwd.or(fallback.cwd),
+            pass_env: self.pass_env.or(fallback.pass_env),
+        }
+    }
+
+    /// Build the policy, using the defaults for unset settings
+    pub fn policy(&self) -> SubstitutionPolicy {
+        let defaults = SubstitutionPolicy::default();
+        SubstitutionPolicy {
+            enabled: self.enabled.unwrap_or(defaults.enabled),
+            allowlist: self.allow.clone().or(defaults.allowlist),
+            timeout: match self.timeout {
+                Some(0) => None,
+                Some(seconds) => Some(Duration::from_secs(seconds)),
+                None => defaults.timeout,
+            },
+            max_output: match self.max_output {
+                Some(0) => None,
+                Some(bytes) => Some(bytes),
+                None => defaults.max_output,
+            },
+            working_dir: self.cwd.unwrap_or(defaults.working_dir),
+            pass_env: self.pass_env.unwrap_or(defaults.pass_env),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layered_policy() {
+        let file = Config::parse(
+            "[substitution]\nallow = [\"git\"]\ntimeout = 0\ncwd = \"env-file\"\npass-env = true\n",
+        )
+        .unwrap()
+        .substitution;
+        let flags = SubstitutionConfig {
+            allow: Some(vec!["date".to_string()]),
+            pass_env: Some(false),
+            ..SubstitutionConfig::default()
+        };
+
+        let policy = flags.or(file).policy();
+        assert_eq!(
+            policy,
+            SubstitutionPolicy {
+                enabled: true,
+                allowlist: Some(vec!["date".to_string()]),
+                timeout: None,
+                max_output: Some(1024 * 1024),
+                working_dir: WorkingDir::EnvFile,
+                pass_env: false,
+            }
+        );
+        assert_eq!(
+            SubstitutionConfig::default().policy(),
+            SubstitutionPolicy::default()
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_settings() {
+        assert!(Config::parse("[substitution]\nalow = [\"git\"]\n").is_err());
+        assert!(Config::parse("[substitution]\ncwd = \"home\"\n").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::crypto::decrypt;
-use crate::parser::DotenvParser;
+use crate::parser::{format_literal_entry, log_warnings, DotenvParser};
+use crate::services::keys::find_private_key;
 use crate::utils::error::{DotenvxError, Result};
 use crate::utils::fs::{read_file, write_file};
 use std::path::Path;
@@ -11,19 +12,27 @@ use tracing::{debug, info};
 ///
 /// * `env_file` - Path to the .env file
 /// * `keys_file` - Optional path to the .env.keys file
+/// * `root` - Discovery root to search for .env.keys files up to, if any
+/// * `strict` - Reject invalid variable names and duplicate keys
 ///
 /// # Returns
 ///
 /// Success message
-pub fn decrypt_file(env_file: &Path, keys_file: Option<&Path>) -> Result<()> {
+pub fn decrypt_file(
+    env_file: &Path,
+    keys_file: Option<&Path>,
+    root: Option<&Path>,
+    strict: bool,
+) -> Result<()> {
     info!("Decrypting file: {}", env_file.display());
 
     let content = read_file(env_file)?;
-    let mut parser = DotenvParser::new();
+    let mut parser = DotenvParser::with_path(env_file).strict(strict);
     parser.parse(&content)?;
+    log_warnings(parser.diagnostics());
 
     // Find the private key
-    let private_key = find_private_key(env_file, keys_file, "DOTENV_PRIVATE_KEY")?;
+    let (private_key, _) = find_private_key(env_file, keys_file, "DOTENV_PRIVATE_KEY", root)?;
 
     // Build decrypted content
     let mut output = String::new();
@@ -52,12 +61,15 @@ pub fn decrypt_file(env_file: &Path, keys_file: Option<&Path>) -> Result<()> {
         if let Some(eq_pos) = line_content.find('=') {
             let key = line_content[..eq_pos].trim();
             let value_part = line_content[eq_pos + 1..].trim();
-            let value = parse_value(value_part);
+            let (value, _) = DotenvParser::parse_value(value_part);
 
             if value.starts_with("encrypted:") {
                 match decrypt(&value, &private_key) {
                     Ok(decrypted) => {
-                        output.push_str(&format!("{}{}=\"{}\"\n", export_prefix, key, decrypted));
+                        // Encrypted values are never expanded, so keep the plaintext literal
+                        output.push_str(export_prefix);
+                        output.push_str(&format_literal_entry(key, &decrypted));
+                        output.push('\n');
                         debug!("Decrypted key: {}", key);
                     }
                     Err(_) => {
@@ -81,54 +93,3 @@ pub fn decrypt_file(env_file: &Path, keys_file: Option<&Path>) -> Result<()> {
     info!("✔ decrypted {}", env_file.display());
     Ok(())
 }
-
-fn parse_value(value: &str) -> String {
-    let value = value.trim();
-    if ((value.starts_with('"') && value.ends_with('"'))
-        || (value.starts_with('\'') && value.ends_with('\'')))
-        && value.len() >= 2
-    {
-        return value[1..value.len() - 1].to_string();
-    }
-    value.to_string()
-}
-
-fn find_private_key(env_file: &Path, keys_file: Option<&Path>, key_name: &str) -> Result<String> {
-    if let Some(keys_path) = keys_file {
-        if keys_path.exists() {
-            let content = read_file(keys_path)?;
-            if let Some(key) = extract_key_from_content(&content, key_name) {
-                return Ok(key);
-            }
-        }
-    }
-
-    if let Some(parent) = env_file.parent() {
-        let default_keys = parent.join(".env.keys");
-        if default_keys.exists() {
-            let content = read_file(&default_keys)?;
-            if let Some(key) = extract_key_from_content(&content, key_name) {
-                return Ok(key);
-            }
-        }
-    }
-
-    if let Ok(key) = std::env::var(key_name) {
-        return Ok(key);
-    }
-
-    Err(DotenvxError::MissingPrivateKey {
-        key_name: key_name.to_string(),
-    })
-}
-
-fn extract_key_from_content(content: &str, key_name: &str) -> Option<String> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with(&format!("{}=", key_name)) {
-            let value = &trimmed[key_name.len() + 1..];
-            return Some(parse_value(value));
-        }
-    }
-    None
-}
@@ -0,0 +1,117 @@
+use crate::utils::error::{DotenvxError, Result};
+use std::path::{Path, PathBuf};
+
+/// An entry marking the top of a repository, where discovery stops
+pub const ROOT_MARKER: &str = ".git";
+
+/// Find the directory that discovery walks up to from `dir`
+///
+/// # Arguments
+///
+/// * `dir` - Directory discovery starts from
+/// * `configured` - Root set with `--root`, which must contain `dir`
+///
+/// # Returns
+///
+/// The configured root, or else the nearest directory at or above `dir`
+/// holding a `.git` file or directory, or `None` if there is neither
+pub fn find_root(dir: &Path, configured: Option<&Path>) -> Result<Option<PathBuf>> {
+    let dir = absolute(dir)?;
+
+    if let Some(root) = configured {
+        let root = absolute(root)?;
+        if !dir.starts_with(&root) {
+            return Err(DotenvxError::Other(format!(
+                "root {} does not contain {}",
+                root.display(),
+                dir.display()
+            )));
+        }
+        return Ok(Some(root));
+    }
+
+    Ok(dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(ROOT_MARKER).exists())
+        .map(Path::to_path_buf))
+}
+
+/// The directories from `root` down to `dir`, outermost first
+///
+/// `dir` is kept as given, so files in it are reported with the paths the
+/// user would write, while the directories above it are absolute. Without a
+/// root, or if `dir` is outside it, only `dir` is searched.
+pub fn search_path(dir: &Path, root: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = vec![dir.to_path_buf()];
+    if let (Some(root), Ok(start)) = (root, absolute(dir)) {
+        if start.starts_with(root) {
+            dirs.extend(
+                start
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|ancestor| ancestor.starts_with(root))
+                    .map(Path::to_path_buf),
+            );
+        }
+    }
+    dirs.reverse();
+    dirs
+}
+
+/// Resolve `path` so it can be compared with others, treating `""` as the
+/// current directory
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    path.canonicalize()
+        .map_err(|e| DotenvxError::Other(format!("cannot resolve {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_root() {
+        let temp = TempDir::new().unwrap();
+        let top = temp.path().canonicalize().unwrap();
+        let service = top.join("services").join("api");
+        fs::create_dir_all(&service).unwrap();
+
+        assert_eq!(find_root(&service, None).unwrap(), None);
+
+        fs::create_dir(top.join(".git")).unwrap();
+        assert_eq!(find_root(&service, None).unwrap(), Some(top.clone()));
+
+        let services = top.join("services");
+        assert_eq!(
+            find_root(&service, Some(&services)).unwrap(),
+            Some(services.clone())
+        );
+        assert!(find_root(&top, Some(&services)).is_err());
+    }
+
+    #[test]
+    fn test_search_path() {
+        let temp = TempDir::new().unwrap();
+        let top = temp.path().canonicalize().unwrap();
+        let service = top.join("services").join("api");
+        fs::create_dir_all(&service).unwrap();
+
+        assert_eq!(
+            search_path(&service, Some(&top)),
+            vec![top.clone(), top.join("services"), service.clone()]
+        );
+        assert_eq!(search_path(&service, None), vec![service.clone()]);
+        assert_eq!(
+            search_path(&top, Some(&service)),
+            vec![top.clone()],
+            "a directory outside the root is searched alone"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::crypto::{encrypt, Keypair};
-use crate::parser::DotenvParser;
+use crate::parser::{format_entry, log_warnings, DotenvParser, Severity, PUBLIC_KEY_BANNER};
+use crate::services::keys::{find_private_key, KeySource};
 use crate::utils::error::{DotenvxError, Result};
 use crate::utils::fs::{read_file, write_file};
 use std::path::Path;
@@ -11,8 +12,15 @@ use tracing::{debug, info};
 ///
 /// * `env_file` - Path to the .env file
 /// * `keys_file` - Optional path to the .env.keys file
+/// * `root` - Discovery root to search for .env.keys files up to, if any
 /// * `specific_keys` - Optional list of specific keys to encrypt
 /// * `exclude_keys` - Optional list of keys to exclude from encryption
+/// * `sensitive_keys` - Keys that are encrypted even when not selected, and
+///   cannot be excluded
+/// * `strict` - Reject invalid variable names
+///
+/// Files with duplicate keys are always rejected, since only one of the
+/// assignments would be encrypted and a plaintext copy could be left behind.
 ///
 /// # Returns
 ///
@@ -20,29 +28,47 @@ use tracing::{debug, info};
 pub fn encrypt_file(
     env_file: &Path,
     keys_file: Option<&Path>,
+    root: Option<&Path>,
     specific_keys: Option<&[String]>,
     exclude_keys: Option<&[String]>,
+    sensitive_keys: &[String],
+    strict: bool,
 ) -> Result<String> {
     info!("Encrypting file: {}", env_file.display());
 
+    if let Some(key) = exclude_keys
+        .unwrap_or_default()
+        .iter()
+        .find(|key| sensitive_keys.contains(key))
+    {
+        return Err(DotenvxError::Other(format!(
+            "cannot exclude '{}' from encryption: the schema marks it @sensitive",
+            key
+        )));
+    }
+
     // Read the .env file
     let content = read_file(env_file)?;
 
     // Parse the file
-    let mut parser = DotenvParser::new();
+    let mut parser = DotenvParser::with_path(env_file)
+        .strict(strict)
+        .duplicates(Severity::Error);
     parser.parse(&content)?;
+    log_warnings(parser.diagnostics());
     let variables = parser.variables().clone();
 
     // Check if already has a public key
-    let keypair = if variables.contains_key("DOTENV_PUBLIC_KEY") {
+    let (keypair, key_source) = if variables.contains_key("DOTENV_PUBLIC_KEY") {
         debug!("Found existing DOTENV_PUBLIC_KEY");
 
         // Try to find the corresponding private key
-        let private_key = find_private_key(env_file, keys_file, "DOTENV_PRIVATE_KEY")?;
-        Keypair::from_private_key(&private_key)?
+        let (private_key, source) =
+            find_private_key(env_file, keys_file, "DOTENV_PRIVATE_KEY", root)?;
+        (Keypair::from_private_key(&private_key)?, Some(source))
     } else {
         debug!("Generating new keypair");
-        Keypair::generate()
+        (Keypair::generate(), None)
     };
     let public_key = keypair.public_key();
 
@@ -50,11 +76,12 @@ pub fn encrypt_file(
     let mut output = String::new();
 
     // Add header
-    output.push_str("#/-------------------[DOTENV_PUBLIC_KEY]--------------------/\n");
-    output.push_str("#/            public-key encryption for .env files          /\n");
-    output.push_str("#/       [how it works](https://dotenvx.com/encryption)     /\n");
-    output.push_str("#/----------------------------------------------------------/\n");
-    output.push_str(&format!("DOTENV_PUBLIC_KEY=\"{}\"\n\n", public_key));
+    for line in PUBLIC_KEY_BANNER {
+        output.push_str(line);
+        output.push('\n');
+    }
+    output.push_str(&format_entry("DOTENV_PUBLIC_KEY", &public_key));
+    output.push_str("\n\n");
 
     // Process each line
     for line in content.lines() {
@@ -87,15 +114,18 @@ pub fn encrypt_file(
             let value_part = line_content[eq_pos + 1..].trim();
 
             // Determine if we should encrypt this key
-            let should_encrypt = should_encrypt_key(key, specific_keys, exclude_keys);
+            let should_encrypt = sensitive_keys.iter().any(|k| k == key)
+                || should_encrypt_key(key, specific_keys, exclude_keys);
 
-            if should_encrypt && !value_part.starts_with("encrypted:") {
-                // Parse the value (remove quotes if present)
-                let value = parse_value(value_part);
+            // Parse the value as it is loaded; backtick commands are encrypted as literal text
+            let (value, _) = DotenvParser::parse_value(value_part);
 
+            if should_encrypt && !value.starts_with("encrypted:") {
                 // Encrypt the value
                 let encrypted = encrypt(&value, &public_key)?;
-                output.push_str(&format!("{}{}=\"{}\"\n", export_prefix, key, encrypted));
+                output.push_str(export_prefix);
+                output.push_str(&format_entry(key, &encrypted));
+                output.push('\n');
                 debug!("Encrypted key: {}", key);
             } else {
                 // Keep as-is
@@ -112,13 +142,16 @@ pub fn encrypt_file(
     // Write the encrypted content
     write_file(env_file, &output)?;
 
-    // Write the keys file if needed
-    write_keys_file(
-        env_file,
-        keys_file,
-        "DOTENV_PRIVATE_KEY",
-        &keypair.private_key(),
-    )?;
+    // Save the key unless it came from a keys file, which may be a shared
+    // one further up
+    if !matches!(key_source, Some(KeySource::File(_))) {
+        write_keys_file(
+            env_file,
+            keys_file,
+            "DOTENV_PRIVATE_KEY",
+            &keypair.private_key(),
+        )?;
+    }
 
     info!("✔ encrypted {}", env_file.display());
     Ok(public_key)
@@ -148,63 +181,6 @@ fn should_encrypt_key(
     true
 }
 
-fn parse_value(value: &str) -> String {
-    let value = value.trim();
-
-    // Remove quotes if present
-    if ((value.starts_with('"') && value.ends_with('"'))
-        || (value.starts_with('\'') && value.ends_with('\'')))
-        && value.len() >= 2
-    {
-        return value[1..value.len() - 1].to_string();
-    }
-
-    value.to_string()
-}
-
-fn find_private_key(env_file: &Path, keys_file: Option<&Path>, key_name: &str) -> Result<String> {
-    // Try the provided keys file first
-    if let Some(keys_path) = keys_file {
-        if keys_path.exists() {
-            let content = read_file(keys_path)?;
-            if let Some(key) = extract_key_from_content(&content, key_name) {
-                return Ok(key);
-            }
-        }
-    }
-
-    // Try .env.keys in the same directory
-    if let Some(parent) = env_file.parent() {
-        let default_keys = parent.join(".env.keys");
-        if default_keys.exists() {
-            let content = read_file(&default_keys)?;
-            if let Some(key) = extract_key_from_content(&content, key_name) {
-                return Ok(key);
-            }
-        }
-    }
-
-    // Try environment variable
-    if let Ok(key) = std::env::var(key_name) {
-        return Ok(key);
-    }
-
-    Err(DotenvxError::MissingPrivateKey {
-        key_name: key_name.to_string(),
-    })
-}
-
-fn extract_key_from_content(content: &str, key_name: &str) -> Option<String> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with(&format!("{}=", key_name)) {
-            let value = &trimmed[key_name.len() + 1..];
-            return Some(parse_value(value));
-        }
-    }
-    None
-}
-
 fn write_keys_file(
     env_file: &Path,
     keys_file: Option<&Path>,
@@ -263,7 +239,7 @@ mod tests {
 
         write_file(&env_file, "SECRET=my_secret_value").unwrap();
 
-        let public_key = encrypt_file(&env_file, None, None, None).unwrap();
+        let public_key = encrypt_file(&env_file, None, None, None, None, &[], false).unwrap();
         assert_eq!(public_key.len(), 66);
 
         let content = read_file(&env_file).unwrap();
@@ -280,10 +256,101 @@ mod tests {
         write_file(&env_file, "KEY1=value1\nKEY2=value2").unwrap();
 
         let keys = vec!["KEY1".to_string()];
-        encrypt_file(&env_file, None, Some(&keys), None).unwrap();
+        encrypt_file(&env_file, None, None, Some(&keys), None, &[], false).unwrap();
 
         let content = read_file(&env_file).unwrap();
         assert!(content.contains("KEY1=\"encrypted:"));
         assert!(content.contains("KEY2=value2"));
     }
+
+    #[test]
+    fn test_encrypt_sensitive_keys() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+
+        write_file(&env_file, "KEY1=value1\nTOKEN=value2\nKEY3=value3").unwrap();
+
+        let sensitive = vec!["TOKEN".to_string()];
+        let result = encrypt_file(
+            &env_file,
+            None,
+            None,
+            None,
+            Some(&sensitive),
+            &sensitive,
+            false,
+        );
+        assert!(result.is_err());
+
+        let keys = vec!["KEY1".to_string()];
+        encrypt_file(&env_file, None, None, Some(&keys), None, &sensitive, false).unwrap();
+
+        let content = read_file(&env_file).unwrap();
+        assert!(content.contains("KEY1=\"encrypted:"));
+        assert!(content.contains("TOKEN=\"encrypted:"));
+        assert!(content.contains("KEY3=value3"));
+    }
+
+    #[test]
+    fn test_encrypt_refuses_duplicates() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+
+        write_file(&env_file, "SECRET=one\nSECRET=two\n").unwrap();
+
+        let result = encrypt_file(&env_file, None, None, None, None, &[], false);
+        assert!(matches!(result, Err(DotenvxError::InvalidEnvFile { .. })));
+        assert_eq!(read_file(&env_file).unwrap(), "SECRET=one\nSECRET=two\n");
+        assert!(!temp.path().join(".env.keys").exists());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_encrypt_backticks_as_literal() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let marker = temp.path().join("marker");
+
+        write_file(&env_file, &format!("CMD=`touch {}`", marker.display())).unwrap();
+        encrypt_file(&env_file, None, None, None, None, &[], false).unwrap();
+        assert!(!marker.exists());
+
+        let content = read_file(&env_file).unwrap();
+        let mut parser = DotenvParser::new();
+        parser.parse(&content).unwrap();
+        let (private_key, _) =
+            find_private_key(&env_file, None, "DOTENV_PRIVATE_KEY", None).unwrap();
+        let decrypted = crate::crypto::decrypt(&parser.variables()["CMD"], &private_key).unwrap();
+        assert_eq!(decrypted, format!("touch {}", marker.display()));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let content = concat!(
+            "NEWLINE=\"line1\\nline2\"\n",
+            "QUOTE=\"say \\\"hi\\\"\"\n",
+            "BACKSLASH=\"C:\\\\temp\"\n",
+            "BACKTICK=\"run `date` now\"\n",
+        );
+        write_file(&env_file, content).unwrap();
+        let parse = |content: &str| {
+            let mut parser = DotenvParser::new();
+            parser.parse(content).unwrap();
+            parser.variables().clone()
+        };
+        let original = parse(content);
+
+        encrypt_file(&env_file, None, None, None, None, &[], false).unwrap();
+        // Encrypting again leaves the already encrypted values alone
+        let encrypted = parse(&read_file(&env_file).unwrap());
+        encrypt_file(&env_file, None, None, None, None, &[], false).unwrap();
+        assert_eq!(parse(&read_file(&env_file).unwrap()), encrypted);
+
+        crate::services::decrypt_file(&env_file, None, None, false).unwrap();
+        let decrypted = read_file(&env_file).unwrap();
+        assert!(decrypted.contains(content), "{}", decrypted);
+        assert_eq!(parse(&decrypted), original);
+    }
 }
@@ -0,0 +1,186 @@
+use crate::services::load::{LoadedEnv, Provenance, Source, Transform};
+use crate::utils::error::{DotenvxError, Result};
+
+/// Describe where variables came from
+///
+/// For each variable this shows the assignment that won, with the
+/// transformations applied to it, and every assignment it shadowed. A full
+/// report starts with the files that were loaded, in order, and the keys
+/// file or variable each was decrypted with.
+///
+/// # Arguments
+///
+/// * `env` - Environment prepared with provenance
+/// * `key` - A single variable to explain, or `None` for every variable set
+///   by a file or inline
+/// * `reveal` - Show values instead of masking them
+///
+/// # Returns
+///
+/// The report, one block per variable, or an error if `key` is not set anywhere
+pub fn explain(env: &LoadedEnv, key: Option<&str>, reveal: bool) -> Result<String> {
+    let mut keys: Vec<&String> = match key {
+        Some(key) => {
+            let (key, _) =
+                env.provenance
+                    .get_key_value(key)
+                    .ok_or_else(|| DotenvxError::MissingKey {
+                        key: key.to_string(),
+                    })?;
+            vec![key]
+        }
+        // The rest of the environment is passed through untouched
+        None => env
+            .provenance
+            .iter()
+            .filter(|(_, assignments)| assignments.iter().any(|a| a.source != Source::Process))
+            .map(|(key, _)| key)
+            .collect(),
+    };
+    keys.sort();
+
+    let mut output = String::new();
+    if key.is_none() {
+        for (path, key_source) in &env.files {
+            match key_source {
+                Some(key_source) => output.push_str(&format!(
+                    "loaded {} (key from {})\n",
+                    path.display(),
+                    key_source
+                )),
+                None => output.push_str(&format!("loaded {}\n", path.display())),
+            }
+        }
+    }
+
+    for key in keys {
+        let mut assignments = env.provenance[key].iter().rev();
+        let Some(winner) = assignments.next() else {
+            continue;
+        };
+
+        if env.variables.contains_key(key) {
+            output.push_str(&format!(
+                "{}={}\n",
+                key,
+                display_value(&winner.value, reveal)
+            ));
+        } else {
+            output.push_str(&format!("{} (withheld from the command)\n", key));
+        }
+        output.push_str(&format!("  from {}\n", describe(env, winner)));
+        for shadowed in assignments {
+            output.push_str(&format!(
+                "  over {} = {}\n",
+                describe(env, shadowed),
+                display_value(&shadowed.value, reveal)
+            ));
+        }
+    }
+    Ok(output)
+}
+
+fn describe(env: &LoadedEnv, assignment: &Provenance) -> String {
+    if assignment.transforms.is_empty() {
+        return assignment.source.to_string();
+    }
+    let key_source = match &assignment.source {
+        Source::File { path, .. } => env
+            .files
+            .iter()
+            .find(|(file, _)| file == path)
+            .and_then(|(_, key_source)| key_source.as_ref()),
+        _ => None,
+    };
+    let transforms: Vec<String> = assignment
+        .transforms
+        .iter()
+        .map(|transform| match (transform, key_source) {
+            (Transform::Decrypted, Some(key_source)) => {
+                format!("{} with {}", transform, key_source)
+            }
+            _ => transform.to_string(),
+        })
+        .collect();
+    format!("{} ({})", assignment.source, transforms.join(", "))
+}
+
+/// The value, or a mask that only shows its length
+fn display_value(value: &str, reveal: bool) -> String {
+    if reveal {
+        format!("{:?}", value)
+    } else if value.is_empty() {
+        "(empty)".to_string()
+    } else {
+        format!("******** ({} chars)", value.chars().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::keys::KeySource;
+    use std::path::PathBuf;
+
+    fn assignment(source: Source, value: &str, transforms: Vec<Transform>) -> Provenance {
+        Provenance {
+            source,
+            value: value.to_string(),
+            transforms,
+        }
+    }
+
+    fn file(path: &str, line: usize) -> Source {
+        Source::File {
+            path: PathBuf::from(path),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut env = LoadedEnv::default();
+        env.provenance.insert(
+            "API_URL".to_string(),
+            vec![
+                assignment(file("/repo/.env", 2), "http://localhost", vec![]),
+                assignment(
+                    file(".env.production", 5),
+                    "https://api.example.com",
+                    vec![Transform::Decrypted],
+                ),
+                assignment(Source::Inline, "https://staging", vec![]),
+            ],
+        );
+        env.provenance.insert(
+            "HOME".to_string(),
+            vec![assignment(Source::Process, "/root", vec![])],
+        );
+        env.variables
+            .insert("API_URL".to_string(), "https://staging".to_string());
+        env.variables
+            .insert("HOME".to_string(), "/root".to_string());
+        env.files = vec![
+            (PathBuf::from("/repo/.env"), None),
+            (
+                PathBuf::from(".env.production"),
+                Some(KeySource::File(PathBuf::from("/repo/.env.keys"))),
+            ),
+        ];
+
+        assert_eq!(
+            explain(&env, None, false).unwrap(),
+            "loaded /repo/.env\n\
+             loaded .env.production (key from /repo/.env.keys)\n\
+             API_URL=******** (15 chars)\n  \
+             from inline -e\n  \
+             over .env.production:5 (decrypted with /repo/.env.keys) = ******** (23 chars)\n  \
+             over /repo/.env:2 = ******** (16 chars)\n"
+        );
+        assert_eq!(
+            explain(&env, Some("HOME"), true).unwrap(),
+            "HOME=\"/root\"\n  from process environment\n"
+        );
+        assert!(explain(&env, Some("MISSING"), false).is_err());
+    }
+}
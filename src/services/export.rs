@@ -0,0 +1,363 @@
+use crate::parser::DotenvParser;
+use crate::utils::error::{DotenvxError, Result};
+use base64::{engine::general_purpose, Engine as _};
+use clap::ValueEnum;
+use glob::Pattern;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Kind of deployment manifest to produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportTarget {
+    /// A Kubernetes Secret with base64 `data:`
+    K8sSecret,
+    /// A Kubernetes ConfigMap
+    K8sConfigmap,
+    /// A docker-compose service `environment:` block
+    Compose,
+}
+
+/// How `export` builds manifests
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Manifest kind
+    pub target: ExportTarget,
+    /// Resource name, or service name for compose
+    pub name: String,
+    /// Kubernetes namespace
+    pub namespace: Option<String>,
+    /// Glob patterns of keys to put in a Secret, the rest going to a ConfigMap
+    pub secret_keys: Vec<String>,
+}
+
+/// Render loaded variables as a manifest
+///
+/// # Arguments
+///
+/// * `env_vars` - Decrypted variables
+/// * `options` - Target, naming and key routing
+///
+/// # Returns
+///
+/// YAML, with one document per Kubernetes resource
+pub fn render_manifest(
+    env_vars: &HashMap<String, String>,
+    options: &ExportOptions,
+) -> Result<String> {
+    let sorted: BTreeMap<&str, &str> = env_vars
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+
+    if options.target == ExportTarget::Compose {
+        if !options.secret_keys.is_empty() {
+            return Err(DotenvxError::Other(
+                "--secret-keys is only supported for Kubernetes targets".to_string(),
+            ));
+        }
+        let mut output = String::new();
+        let _ = writeln!(output, "services:");
+        let _ = writeln!(output, "  {}:", yaml_string(&options.name));
+        let _ = writeln!(output, "    environment:");
+        for (key, value) in sorted {
+            // Compose interpolates `$`, which `$$` escapes
+            let _ = writeln!(
+                output,
+                "      {}: {}",
+                yaml_string(key),
+                yaml_string(&value.replace('$', "$$"))
+            );
+        }
+        return Ok(output);
+    }
+
+    validate_name(&options.name)?;
+    if let Some(namespace) = &options.namespace {
+        validate_name(namespace)?;
+    }
+
+    let (secret, config): (BTreeMap<_, _>, BTreeMap<_, _>) = if options.secret_keys.is_empty() {
+        match options.target {
+            ExportTarget::K8sSecret => (sorted, BTreeMap::new()),
+            _ => (BTreeMap::new(), sorted),
+        }
+    } else {
+        let patterns = options
+            .secret_keys
+            .iter()
+            .map(|p| Pattern::new(p).map_err(|e| DotenvxError::Other(e.to_string())))
+            .collect::<Result<Vec<_>>>()?;
+        sorted
+            .into_iter()
+            .partition(|(key, _)| patterns.iter().any(|p| p.matches(key)))
+    };
+
+    let mut documents = Vec::new();
+    if !config.is_empty() || options.target == ExportTarget::K8sConfigmap {
+        let data = config.iter().map(|(k, v)| (*k, yaml_string(v)));
+        documents.push(k8s_resource("ConfigMap", options, data));
+    }
+    if !secret.is_empty() || options.target == ExportTarget::K8sSecret {
+        let data = secret
+            .iter()
+            .map(|(k, v)| (*k, yaml_string(&general_purpose::STANDARD.encode(v))));
+        documents.push(k8s_resource("Secret", options, data));
+    }
+
+    Ok(documents.join("---\n"))
+}
+
+/// Render .env files as a ConfigMap that keeps their ciphertext, plus the
+/// init container that decrypts them when the pod starts
+///
+/// Every value except `DOTENV_PUBLIC_KEY` must be encrypted, since the
+/// ConfigMap holds the files as written.
+///
+/// # Arguments
+///
+/// * `files` - File names and their raw, encrypted content
+/// * `options` - Naming; the target must be [`ExportTarget::K8sConfigmap`]
+///
+/// # Returns
+///
+/// YAML for the ConfigMap, followed by the init container as a comment
+pub fn render_sealed(files: &[(String, String)], options: &ExportOptions) -> Result<String> {
+    if options.target != ExportTarget::K8sConfigmap {
+        return Err(DotenvxError::Other(
+            "--sealed writes a ConfigMap of ciphertext; use --to k8s-configmap".to_string(),
+        ));
+    }
+    validate_name(&options.name)?;
+    if let Some(namespace) = &options.namespace {
+        validate_name(namespace)?;
+    }
+
+    for (index, (name, content)) in files.iter().enumerate() {
+        if files[..index].iter().any(|(other, _)| other == name) {
+            return Err(DotenvxError::Other(format!(
+                "cannot seal two files named '{}': each file becomes a ConfigMap key",
+                name
+            )));
+        }
+
+        let mut parser = DotenvParser::with_path(name);
+        parser.parse(content)?;
+        let plaintext: Vec<&str> = parser
+            .entries()
+            .iter()
+            .filter(|entry| !entry.encrypted && entry.key != "DOTENV_PUBLIC_KEY")
+            .map(|entry| entry.key.as_str())
+            .collect();
+        if !plaintext.is_empty() {
+            return Err(DotenvxError::Other(format!(
+                "cannot seal {}: {} not encrypted; run `dotenvx encrypt` first",
+                name,
+                plaintext.join(", ")
+            )));
+        }
+    }
+
+    // ConfigMap keys are restricted to these characters, which are also shell-safe
+    if let Some((name, _)) = files.iter().find(|(name, _)| {
+        !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }) {
+        return Err(DotenvxError::Other(format!(
+            "cannot seal '{}': file names may only contain letters, digits, '-', '_' and '.'",
+            name
+        )));
+    }
+
+    let data = files
+        .iter()
+        .map(|(name, content)| (name.as_str(), yaml_string(content)));
+    let mut output = k8s_resource("ConfigMap", options, data);
+
+    let env_files: String = files
+        .iter()
+        .map(|(name, _)| format!(" -f /etc/dotenvx/{}", name))
+        .collect();
+    let command = format!(
+        "dotenvx printenv --format dotenv{} > /run/dotenvx/.env",
+        env_files
+    );
+
+    let _ = write!(
+        output,
+        "# Add to the pod spec. The private key is read from the Secret
+# \"{name}-key\", which must be created separately:
+#
+# initContainers:
+#   - name: dotenvx-decrypt
+#     image: dotenvx
+#     command: [\"sh\", \"-c\", {command}]
+#     env:
+#       - name: DOTENV_PRIVATE_KEY
+#         valueFrom:
+#           secretKeyRef:
+#             name: {name}-key
+#             key: DOTENV_PRIVATE_KEY
+#     volumeMounts:
+#       - {{ name: dotenvx-sealed, mountPath: /etc/dotenvx }}
+#       - {{ name: dotenvx-env, mountPath: /run/dotenvx }}
+# volumes:
+#   - {{ name: dotenvx-sealed, configMap: {{ name: {name} }} }}
+#   - {{ name: dotenvx-env, emptyDir: {{ medium: Memory }} }}
+",
+        name = options.name,
+        command = yaml_string(&command),
+    );
+
+    Ok(output)
+}
+
+fn k8s_resource<'a>(
+    kind: &str,
+    options: &ExportOptions,
+    data: impl Iterator<Item = (&'a str, String)>,
+) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "apiVersion: v1");
+    let _ = writeln!(output, "kind: {}", kind);
+    let _ = writeln!(output, "metadata:");
+    let _ = writeln!(output, "  name: {}", options.name);
+    if let Some(namespace) = &options.namespace {
+        let _ = writeln!(output, "  namespace: {}", namespace);
+    }
+    if kind == "Secret" {
+        let _ = writeln!(output, "type: Opaque");
+    }
+
+    let mut data = data.peekable();
+    if data.peek().is_none() {
+        let _ = writeln!(output, "data: {{}}");
+    } else {
+        let _ = writeln!(output, "data:");
+        for (key, value) in data {
+            let _ = writeln!(output, "  {}: {}", yaml_string(key), value);
+        }
+    }
+    output
+}
+
+/// Kubernetes names are lowercase DNS subdomains
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 253
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(DotenvxError::Other(format!(
+            "invalid Kubernetes name '{}': use lowercase letters, digits, '-' and '.'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// JSON strings are valid double-quoted YAML scalars
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(target: ExportTarget) -> ExportOptions {
+        ExportOptions {
+            target,
+            name: "web".to_string(),
+            namespace: Some("prod".to_string()),
+            secret_keys: Vec::new(),
+        }
+    }
+
+    fn vars() -> HashMap<String, String> {
+        [("DB_PASSWORD", "p@ss"), ("PORT", "8080"), ("PRICE", "$5")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_secret() {
+        let yaml = render_manifest(&vars(), &options(ExportTarget::K8sSecret)).unwrap();
+        assert_eq!(
+            yaml,
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: web\n  namespace: prod\n\
+             type: Opaque\ndata:\n  \"DB_PASSWORD\": \"cEBzcw==\"\n  \"PORT\": \"ODA4MA==\"\n  \
+             \"PRICE\": \"JDU=\"\n"
+        );
+    }
+
+    #[test]
+    fn test_routing() {
+        let mut options = options(ExportTarget::K8sConfigmap);
+        options.secret_keys = vec!["*_PASSWORD".to_string()];
+        let yaml = render_manifest(&vars(), &options).unwrap();
+
+        let documents: Vec<&str> = yaml.split("---\n").collect();
+        assert_eq!(documents.len(), 2);
+        assert!(documents[0].contains("kind: ConfigMap"));
+        assert!(documents[0].contains("\"PORT\": \"8080\""));
+        assert!(!documents[0].contains("DB_PASSWORD"));
+        assert!(documents[1].contains("kind: Secret"));
+        assert!(documents[1].contains("\"DB_PASSWORD\": \"cEBzcw==\""));
+    }
+
+    #[test]
+    fn test_compose() {
+        let yaml = render_manifest(&vars(), &options(ExportTarget::Compose)).unwrap();
+        assert_eq!(
+            yaml,
+            "services:\n  \"web\":\n    environment:\n      \"DB_PASSWORD\": \"p@ss\"\n      \
+             \"PORT\": \"8080\"\n      \"PRICE\": \"$$5\"\n"
+        );
+    }
+
+    #[test]
+    fn test_sealed() {
+        let files = vec![(".env".to_string(), "KEY=\"encrypted:abc\"\n".to_string())];
+        let yaml = render_sealed(&files, &options(ExportTarget::K8sConfigmap)).unwrap();
+        assert!(yaml.contains("kind: ConfigMap"));
+        assert!(yaml.contains("\".env\": \"KEY=\\\"encrypted:abc\\\"\\n\""));
+        assert!(yaml.contains("dotenvx printenv --format dotenv -f /etc/dotenvx/.env"));
+        assert!(yaml.contains("name: web-key"));
+
+        assert!(render_sealed(&files, &options(ExportTarget::K8sSecret)).is_err());
+        assert!(render_sealed(&files, &options(ExportTarget::Compose)).is_err());
+    }
+
+    #[test]
+    fn test_sealed_refuses_plaintext_and_duplicates() {
+        let options = options(ExportTarget::K8sConfigmap);
+        let sealed = |files: &[(&str, &str)]| {
+            let files: Vec<(String, String)> = files
+                .iter()
+                .map(|(name, content)| (name.to_string(), content.to_string()))
+                .collect();
+            render_sealed(&files, &options)
+        };
+
+        assert!(sealed(&[(".env", "DOTENV_PUBLIC_KEY=\"02ab\"\nKEY=encrypted:abc\n")]).is_ok());
+        let error = sealed(&[(".env", "KEY=encrypted:abc\nDB_PASSWORD=hunter2\n")]).unwrap_err();
+        assert!(error.to_string().contains("DB_PASSWORD not encrypted"));
+        assert!(sealed(&[
+            (".env", "KEY=encrypted:abc\n"),
+            (".env", "B=encrypted:def\n")
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let mut options = options(ExportTarget::K8sSecret);
+        options.name = "Web App".to_string();
+        assert!(render_manifest(&vars(), &options).is_err());
+    }
+}
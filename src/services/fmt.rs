@@ -0,0 +1,281 @@
+use crate::parser::document::{Document, EntryLine, Line};
+use crate::parser::{log_warnings, DotenvParser, Quote, PUBLIC_KEY_BANNER};
+use crate::utils::error::Result;
+use crate::utils::fs::{read_file, write_file};
+use clap::ValueEnum;
+use std::path::Path;
+use tracing::info;
+
+/// When values are wrapped in quotes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuoteStyle {
+    /// Quote only values that would otherwise be read differently
+    #[default]
+    Needed,
+    /// Quote every value
+    Always,
+}
+
+/// When assignments carry the `export` keyword
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportStyle {
+    /// Export every key if any key is exported, otherwise none
+    #[default]
+    Auto,
+    /// Export every key
+    Always,
+    /// Export no key
+    Never,
+}
+
+/// How `format` rewrites a file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatOptions {
+    /// Quoting style for values
+    pub quote: QuoteStyle,
+    /// Use of the `export` keyword
+    pub export: ExportStyle,
+    /// Sort keys alphabetically within each comment-delimited section
+    pub sort: bool,
+}
+
+/// Rewrite .env content in canonical style
+///
+/// Values keep their meaning: a value is only requoted when the parser would
+/// read the new form identically, and `encrypted:` values and the public key
+/// are left exactly as written.
+///
+/// # Arguments
+///
+/// * `content` - The content of the .env file
+/// * `options` - Formatting choices
+///
+/// # Returns
+///
+/// The formatted content
+pub fn format(content: &str, options: &FormatOptions) -> String {
+    let mut document = Document::parse(content);
+
+    // Lift the public key out, to be put back under its banner at the top
+    let mut public_key = None;
+    document.lines.retain(|line| match line {
+        Line::Entry(entry) if entry.key == "DOTENV_PUBLIC_KEY" => {
+            public_key = Some(entry.clone());
+            false
+        }
+        Line::Comment(raw) => !PUBLIC_KEY_BANNER.contains(&raw.trim()),
+        _ => true,
+    });
+
+    let export = match options.export {
+        ExportStyle::Always => true,
+        ExportStyle::Never => false,
+        ExportStyle::Auto => document.entries().any(|(_, entry)| entry.is_exported()),
+    };
+    for entry in document.entries_mut() {
+        normalize_entry(entry, export, options.quote);
+    }
+    for line in document.lines.iter_mut() {
+        if let Line::Blank(raw) | Line::Comment(raw) = line {
+            raw.truncate(raw.trim_end().len());
+        }
+    }
+
+    if options.sort {
+        document.sort_sections();
+    }
+
+    // Collapse runs of blank lines and drop them at both ends, and separate
+    // each comment block that starts a section with exactly one blank line
+    let mut lines: Vec<Line> = Vec::with_capacity(document.lines.len());
+    for line in document.lines {
+        match (&line, lines.last()) {
+            (Line::Blank(_), None | Some(Line::Blank(_))) => continue,
+            (Line::Comment(_), Some(Line::Entry(_))) => lines.push(Line::Blank(String::new())),
+            _ => {}
+        }
+        lines.push(line);
+    }
+    if matches!(lines.last(), Some(Line::Blank(_))) {
+        lines.pop();
+    }
+
+    if let Some(mut entry) = public_key {
+        normalize_entry(&mut entry, false, options.quote);
+        let mut header: Vec<Line> = PUBLIC_KEY_BANNER
+            .iter()
+            .map(|line| Line::Comment(line.to_string()))
+            .collect();
+        header.push(Line::Entry(entry));
+        if !lines.is_empty() {
+            header.push(Line::Blank(String::new()));
+        }
+        lines.splice(0..0, header);
+    }
+
+    document.lines = lines;
+    document.final_newline = true;
+    document.to_string()
+}
+
+fn normalize_entry(entry: &mut EntryLine, export: bool, style: QuoteStyle) {
+    entry.prefix = if export {
+        "export ".to_string()
+    } else {
+        String::new()
+    };
+    entry.before_eq.clear();
+    entry.after_eq.clear();
+    entry.trailing.clear();
+
+    let (value, quote) = entry.value();
+    if value.starts_with("encrypted:") || entry.key == "DOTENV_PUBLIC_KEY" {
+        return;
+    }
+    if let Some(raw_value) = requote(&value, quote, style) {
+        entry.raw_value = raw_value;
+    }
+}
+
+/// The canonical form of a value, or `None` to keep it as written
+fn requote(value: &str, quote: Quote, style: QuoteStyle) -> Option<String> {
+    match quote {
+        // Command text must stay in backticks
+        Quote::Backtick => None,
+        // Single quotes suppress expansion, so only drop them when nothing would expand
+        Quote::Single if value.contains('$') => None,
+        Quote::Single | Quote::Double | Quote::None => {
+            if style == QuoteStyle::Needed && !needs_quotes(value) {
+                Some(value.to_string())
+            } else {
+                Some(format!("\"{}\"", DotenvParser::escape(value)))
+            }
+        }
+    }
+}
+
+/// Whether a value must be quoted to be read back unchanged
+pub fn needs_quotes(value: &str) -> bool {
+    value
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '#' | '"' | '\'' | '`' | '\\'))
+}
+
+/// Format a .env file in place, or only check whether it is formatted
+///
+/// # Arguments
+///
+/// * `env_file` - Path to the .env file
+/// * `options` - Formatting choices
+/// * `check` - Leave the file untouched and only report
+/// * `strict` - Reject invalid variable names and duplicate keys
+///
+/// # Returns
+///
+/// Whether the file was (or, with `check`, would be) changed
+pub fn format_file(
+    env_file: &Path,
+    options: &FormatOptions,
+    check: bool,
+    strict: bool,
+) -> Result<bool> {
+    let content = read_file(env_file)?;
+
+    // Refuse to reformat files that do not parse
+    let mut parser = DotenvParser::with_path(env_file).strict(strict);
+    parser.parse(&content)?;
+    log_warnings(parser.diagnostics());
+
+    let formatted = format(&content, options);
+    if formatted == content {
+        return Ok(false);
+    }
+
+    if !check {
+        write_file(env_file, &formatted)?;
+        info!("✔ formatted {}", env_file.display());
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> std::collections::HashMap<String, String> {
+        let mut parser = DotenvParser::new();
+        parser.parse(content).unwrap();
+        parser.variables().clone()
+    }
+
+    #[test]
+    fn test_format_canonical() {
+        let content = "\n\n# app\nexport  NAME = 'web'   \nMSG=\"hello world\"\n\n\n\nexport PORT=\"8080\"\nHOME_DIR=C:\\users\\me\n\n";
+        let formatted = format(content, &FormatOptions::default());
+        assert_eq!(
+            formatted,
+            "# app\nexport NAME=web\nexport MSG=\"hello world\"\n\nexport PORT=8080\nexport HOME_DIR=\"C:\\\\users\\\\me\"\n"
+        );
+        assert_eq!(parse(content), parse(&formatted));
+    }
+
+    #[test]
+    fn test_format_always_quote_and_sort() {
+        let options = FormatOptions {
+            quote: QuoteStyle::Always,
+            export: ExportStyle::Never,
+            sort: true,
+        };
+        let content = "export B=2\nA='$HOME'\n# other\nD=`date`\nC=3\n";
+        assert_eq!(
+            format(content, &options),
+            "A='$HOME'\nB=\"2\"\n\n# other\nC=\"3\"\nD=`date`\n"
+        );
+    }
+
+    #[test]
+    fn test_format_section_spacing() {
+        let expected = "# db\nHOST=db\nPORT=5432\n\n# cache\n# shared\nCACHE=redis\n";
+        for content in [
+            "# db\nHOST=db\nPORT=5432\n# cache\n# shared\nCACHE=redis\n",
+            "# db\nHOST=db\nPORT=5432\n\n\n\n# cache\n# shared\nCACHE=redis\n",
+        ] {
+            assert_eq!(format(content, &FormatOptions::default()), expected);
+        }
+    }
+
+    #[test]
+    fn test_format_banner_and_ciphertext() {
+        let ciphertext = "encrypted:BE9Y7LKANx77X1pv1HnEoil93fPa5c9rBL";
+        let content = format!(
+            "FOO=bar\nSECRET= {}  \n{}\nDOTENV_PUBLIC_KEY=\"02abc\"\n",
+            ciphertext,
+            PUBLIC_KEY_BANNER.join("\n")
+        );
+        let formatted = format(&content, &FormatOptions::default());
+        let expected = format!(
+            "{}\nDOTENV_PUBLIC_KEY=\"02abc\"\n\nFOO=bar\nSECRET={}\n",
+            PUBLIC_KEY_BANNER.join("\n"),
+            ciphertext
+        );
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted, &FormatOptions::default()), formatted);
+    }
+
+    #[test]
+    fn test_format_file_check() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "A = 1").unwrap();
+
+        let options = FormatOptions::default();
+        assert!(format_file(&env_file, &options, true, false).unwrap());
+        assert_eq!(read_file(&env_file).unwrap(), "A = 1");
+        assert!(format_file(&env_file, &options, false, false).unwrap());
+        assert_eq!(read_file(&env_file).unwrap(), "A=1\n");
+        assert!(!format_file(&env_file, &options, true, false).unwrap());
+
+        write_file(&env_file, "INVALID").unwrap();
+        assert!(format_file(&env_file, &options, true, false).is_err());
+    }
+}
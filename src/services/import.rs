@@ -0,0 +1,503 @@
+use crate::parser::is_valid_key;
+use crate::utils::error::{DotenvxError, Result};
+use base64::{engine::general_purpose, Engine as _};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Format of a file to import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// A JSON object, flattened into keys
+    Json,
+    /// A YAML mapping, flattened into keys
+    Yaml,
+    /// A TOML document, flattened into keys
+    Toml,
+    /// `export KEY=value` lines as written by a shell script
+    Shell,
+    /// A `docker run --env-file` file
+    Docker,
+    /// A Kubernetes Secret manifest, with base64 `data:` decoded
+    K8sSecret,
+}
+
+impl ImportFormat {
+    /// Infer the format from a file extension
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to import
+    ///
+    /// # Returns
+    ///
+    /// The format, or `None` if the extension is not recognised
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "sh" | "bash" | "zsh" => Some(Self::Shell),
+            "env" | "list" => Some(Self::Docker),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a file into variables to import
+///
+/// Structured formats are flattened: nested keys are joined with
+/// `separator`, upper-cased, and characters that cannot appear in a variable
+/// name are replaced with `_`. Arrays of scalars become comma-separated
+/// values, other arrays are indexed, and `null` becomes an empty value.
+/// Shell and docker values are taken literally, without expansion.
+///
+/// # Arguments
+///
+/// * `content` - File content
+/// * `format` - How to read the content
+/// * `separator` - Joins the keys of nested values
+///
+/// # Returns
+///
+/// Variables in the order they should be written
+pub fn parse_import(
+    content: &str,
+    format: ImportFormat,
+    separator: &str,
+) -> Result<Vec<(String, String)>> {
+    let pairs = match format {
+        ImportFormat::Json => {
+            let value: Value = serde_json::from_str(content)
+                .map_err(|e| DotenvxError::Deserialize(e.to_string()))?;
+            flatten_root(&value, separator)?
+        }
+        ImportFormat::Yaml => {
+            let value: Value = serde_norway::from_str(content)
+                .map_err(|e| DotenvxError::Deserialize(e.to_string()))?;
+            flatten_root(&value, separator)?
+        }
+        ImportFormat::Toml => {
+            let value: toml::Value =
+                toml::from_str(content).map_err(|e| DotenvxError::Deserialize(e.to_string()))?;
+            flatten_root(&toml_to_json(value), separator)?
+        }
+        ImportFormat::K8sSecret => parse_secret(content)?,
+        ImportFormat::Shell => return parse_shell(content),
+        ImportFormat::Docker => return parse_docker(content),
+    };
+
+    normalize_keys(pairs)
+}
+
+/// Capture process environment variables whose names start with `prefix`
+///
+/// Names are kept as they are, prefix included.
+///
+/// # Arguments
+///
+/// * `prefix` - Name prefix to match
+///
+/// # Returns
+///
+/// Matching variables, sorted by name
+pub fn from_env(prefix: &str) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(key, _)| key.starts_with(prefix))
+        .collect();
+    vars.sort();
+    vars
+}
+
+fn flatten_root(value: &Value, separator: &str) -> Result<Vec<(String, String)>> {
+    if !value.is_object() {
+        return Err(DotenvxError::Deserialize(
+            "expected a mapping of keys to values at the top level".to_string(),
+        ));
+    }
+    let mut pairs = Vec::new();
+    flatten("", value, separator, &mut pairs);
+    Ok(pairs)
+}
+
+fn flatten(prefix: &str, value: &Value, separator: &str, pairs: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}{}{}", prefix, separator, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(&join(key), value, separator, pairs);
+            }
+        }
+        Value::Array(items)
+            if items
+                .iter()
+                .all(|item| !item.is_object() && !item.is_array()) =>
+        {
+            let joined: Vec<String> = items.iter().map(scalar).collect();
+            pairs.push((prefix.to_string(), joined.join(",")));
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(&join(&index.to_string()), item, separator, pairs);
+            }
+        }
+        _ => pairs.push((prefix.to_string(), scalar(value))),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Read `data:` (base64) and `stringData:` (plain) from a Secret manifest
+fn parse_secret(content: &str) -> Result<Vec<(String, String)>> {
+    let secret: Value =
+        serde_norway::from_str(content).map_err(|e| DotenvxError::Deserialize(e.to_string()))?;
+    if secret.get("kind").and_then(Value::as_str) != Some("Secret") {
+        return Err(DotenvxError::Deserialize(
+            "expected a manifest with kind: Secret".to_string(),
+        ));
+    }
+
+    let mut pairs = Vec::new();
+    if let Some(data) = secret.get("data").and_then(Value::as_object) {
+        for (key, value) in data {
+            let encoded = value.as_str().unwrap_or_default();
+            let decoded = general_purpose::STANDARD.decode(encoded.trim())?;
+            let decoded = String::from_utf8(decoded).map_err(|_| DotenvxError::InvalidValue {
+                key: key.clone(),
+                message: "secret data is not valid UTF-8".to_string(),
+            })?;
+            pairs.push((key.clone(), decoded));
+        }
+    }
+    // Kubernetes lets stringData override data
+    if let Some(data) = secret.get("stringData").and_then(Value::as_object) {
+        for (key, value) in data {
+            pairs.retain(|(existing, _)| existing != key);
+            pairs.push((key.clone(), scalar(value)));
+        }
+    }
+    Ok(pairs)
+}
+
+/// Upper-case keys and replace characters not allowed in variable names
+fn normalize_keys(pairs: Vec<(String, String)>) -> Result<Vec<(String, String)>> {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut normalized = Vec::with_capacity(pairs.len());
+
+    for (original, value) in pairs {
+        let mut key: String = original
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if !key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            key.insert(0, '_');
+        }
+
+        if let Some(previous) = seen.insert(key.clone(), original.clone()) {
+            return Err(DotenvxError::InvalidValue {
+                key,
+                message: format!("both '{}' and '{}' import as this key", previous, original),
+            });
+        }
+        normalized.push((key, value));
+    }
+
+    Ok(normalized)
+}
+
+/// Parse `KEY=value` assignments, optionally prefixed with `export` or
+/// `declare -x`, honouring shell quoting but not expansion
+fn parse_shell(content: &str) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+    // Whether the current statement began with `export` or `declare`
+    let mut exporting = false;
+
+    loop {
+        // Skip blank lines, indentation and comments
+        match chars.peek() {
+            None => break,
+            Some('\n' | ';') => {
+                line += usize::from(chars.next() == Some('\n'));
+                exporting = false;
+                continue;
+            }
+            Some(c) if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            Some('#') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            _ => {}
+        }
+
+        let start = line;
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            word.push(c);
+        }
+        if matches!(word.as_str(), "export" | "declare") || exporting && word.starts_with('-') {
+            exporting = true;
+            continue;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            // `export KEY` without a value
+            if exporting && is_valid_key(&word) {
+                continue;
+            }
+            return Err(DotenvxError::ParseError {
+                line: start,
+                message: format!("expected KEY=value, found '{}'", word),
+            });
+        }
+        if !is_valid_key(&word) {
+            return Err(DotenvxError::ParseError {
+                line: start,
+                message: format!("invalid variable name '{}'", word),
+            });
+        }
+
+        let mut value = String::new();
+        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != ';') {
+            match c {
+                '\'' => loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            value.push(c);
+                        }
+                        None => return Err(unterminated(start, '\'')),
+                    }
+                },
+                '"' => loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => line += 1,
+                            Some(c @ ('$' | '`' | '"' | '\\')) => value.push(c),
+                            Some(c) => {
+                                value.push('\\');
+                                value.push(c);
+                            }
+                            None => return Err(unterminated(start, '"')),
+                        },
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            value.push(c);
+                        }
+                        None => return Err(unterminated(start, '"')),
+                    }
+                },
+                '\\' => match chars.next() {
+                    Some('\n') => line += 1,
+                    Some(c) => value.push(c),
+                    None => {}
+                },
+                c => value.push(c),
+            }
+        }
+
+        pairs.push((word, value));
+    }
+
+    Ok(pairs)
+}
+
+fn unterminated(line: usize, quote: char) -> DotenvxError {
+    DotenvxError::ParseError {
+        line,
+        message: format!("unterminated {} quote", quote),
+    }
+}
+
+/// Parse a docker env file, where values are literal and a bare `KEY` takes
+/// its value from the current environment
+fn parse_docker(content: &str) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (line.trim_end(), None),
+        };
+        if !is_valid_key(key) {
+            return Err(DotenvxError::ParseError {
+                line: index + 1,
+                message: format!("invalid variable name '{}'", key),
+            });
+        }
+
+        if let Some(value) = value.or_else(|| std::env::var(key).ok()) {
+            pairs.push((key.to_string(), value));
+        }
+    }
+
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_flatten_structured() {
+        let expected = pairs(&[
+            ("DATABASE__HOSTS", "a,b"),
+            ("DATABASE__PORT", "5432"),
+            ("DEBUG", "true"),
+            ("EMPTY", ""),
+            ("SERVERS__0__NAME", "web"),
+            ("SERVICE_NAME", "api"),
+        ]);
+
+        let json = r#"{"database": {"port": 5432, "hosts": ["a", "b"]}, "debug": true,
+            "empty": null, "servers": [{"name": "web"}], "service-name": "api"}"#;
+        assert_eq!(
+            parse_import(json, ImportFormat::Json, "__").unwrap(),
+            expected
+        );
+
+        let yaml = "database:\n  port: 5432\n  hosts: [a, b]\ndebug: true\nempty: ~\n\
+                    servers:\n  - name: web\nservice-name: api\n";
+        assert_eq!(
+            parse_import(yaml, ImportFormat::Yaml, "__").unwrap(),
+            expected
+        );
+
+        let toml = "debug = true\nempty = \"\"\nservice-name = \"api\"\n\
+                    [database]\nport = 5432\nhosts = [\"a\", \"b\"]\n\
+                    [[servers]]\nname = \"web\"\n";
+        assert_eq!(
+            parse_import(toml, ImportFormat::Toml, "__").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_key_collision() {
+        let json = r#"{"a-b": "1", "a_b": "2"}"#;
+        assert!(parse_import(json, ImportFormat::Json, "__").is_err());
+        assert!(parse_import("[1, 2]", ImportFormat::Json, "__").is_err());
+    }
+
+    #[test]
+    fn test_secret() {
+        let manifest = "apiVersion: v1\nkind: Secret\nmetadata:\n  name: web\n\
+                        data:\n  DB_PASSWORD: cEBzcw==\n  PORT: ODA4MA==\n\
+                        stringData:\n  PORT: \"9090\"\n  api.key: abc\n";
+        assert_eq!(
+            parse_import(manifest, ImportFormat::K8sSecret, "__").unwrap(),
+            pairs(&[
+                ("DB_PASSWORD", "p@ss"),
+                ("PORT", "9090"),
+                ("API_KEY", "abc")
+            ])
+        );
+
+        let config_map = "apiVersion: v1\nkind: ConfigMap\ndata:\n  A: b\n";
+        assert!(parse_import(config_map, ImportFormat::K8sSecret, "__").is_err());
+    }
+
+    #[test]
+    fn test_shell() {
+        let script = "#!/bin/sh\n# comment\nexport A=1\ndeclare -x B=\"say \\\"hi\\\" $HOME\"\n\
+                      C='it'\\''s'\nD=multi\\ word; E='line\nbreak'\nexport F\n";
+        assert_eq!(
+            parse_import(script, ImportFormat::Shell, "__").unwrap(),
+            pairs(&[
+                ("A", "1"),
+                ("B", "say \"hi\" $HOME"),
+                ("C", "it's"),
+                ("D", "multi word"),
+                ("E", "line\nbreak"),
+            ])
+        );
+
+        assert!(matches!(
+            parse_import("A=1\nB='open\n", ImportFormat::Shell, "__"),
+            Err(DotenvxError::ParseError { line: 2, .. })
+        ));
+        assert!(parse_import("echo hi\n", ImportFormat::Shell, "__").is_err());
+    }
+
+    #[test]
+    fn test_docker() {
+        std::env::set_var("DOTENVX_TEST_IMPORT_DOCKER", "from env");
+        let content = "# comment\nA=\"quoted\" literally\n  B=\nDOTENVX_TEST_IMPORT_DOCKER\n\
+                       DOTENVX_TEST_IMPORT_UNSET\n";
+        assert_eq!(
+            parse_import(content, ImportFormat::Docker, "__").unwrap(),
+            pairs(&[
+                ("A", "\"quoted\" literally"),
+                ("B", ""),
+                ("DOTENVX_TEST_IMPORT_DOCKER", "from env"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("DOTENVX_TEST_FROM_ENV_B", "2");
+        std::env::set_var("DOTENVX_TEST_FROM_ENV_A", "1");
+        assert_eq!(
+            from_env("DOTENVX_TEST_FROM_ENV_"),
+            pairs(&[
+                ("DOTENVX_TEST_FROM_ENV_A", "1"),
+                ("DOTENVX_TEST_FROM_ENV_B", "2")
+            ])
+        );
+    }
+}
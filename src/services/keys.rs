@@ -0,0 +1,139 @@
+use crate::services::discover::search_path;
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use std::path::{Path, PathBuf};
+
+/// Name of the keys file looked up next to .env files
+pub const KEYS_FILE: &str = ".env.keys";
+
+/// Where a private key was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// A .env.keys file
+    File(PathBuf),
+    /// An environment variable of this name
+    Env(String),
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+/// The .env.keys files that may hold the private key for `env_file`, in the
+/// order they are searched
+///
+/// # Arguments
+///
+/// * `env_file` - The .env file to decrypt
+/// * `keys_file` - Keys file given with `-k`, searched first
+/// * `root` - Discovery root; .env.keys files from the file's directory up to
+///   it are searched, innermost first
+pub fn keys_files(env_file: &Path, keys_file: Option<&Path>, root: Option<&Path>) -> Vec<PathBuf> {
+    let dir = env_file.parent().unwrap_or(Path::new(""));
+    let mut files: Vec<PathBuf> = keys_file.map(Path::to_path_buf).into_iter().collect();
+    files.extend(
+        search_path(dir, root)
+            .into_iter()
+            .rev()
+            .map(|dir| dir.join(KEYS_FILE)),
+    );
+    files
+}
+
+/// Find the private key for an .env file
+///
+/// The keys files from [`keys_files`] are searched in order, then the
+/// environment variable `key_name`.
+///
+/// # Arguments
+///
+/// * `env_file` - The .env file to decrypt
+/// * `keys_file` - Optional path to the .env.keys file
+/// * `key_name` - Name of the key, such as `DOTENV_PRIVATE_KEY`
+/// * `root` - Discovery root to search up to, if any
+///
+/// # Returns
+///
+/// The private key and where it was found
+pub fn find_private_key(
+    env_file: &Path,
+    keys_file: Option<&Path>,
+    key_name: &str,
+    root: Option<&Path>,
+) -> Result<(String, KeySource)> {
+    for keys_path in keys_files(env_file, keys_file, root) {
+        if keys_path.exists() {
+            let content = read_file(&keys_path)?;
+            if let Some(key) = extract_key(&content, key_name) {
+                return Ok((key, KeySource::File(keys_path)));
+            }
+        }
+    }
+
+    if let Ok(key) = std::env::var(key_name) {
+        return Ok((key, KeySource::Env(key_name.to_string())));
+    }
+
+    Err(DotenvxError::MissingPrivateKey {
+        key_name: key_name.to_string(),
+    })
+}
+
+fn extract_key(content: &str, key_name: &str) -> Option<String> {
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with(&format!("{}=", key_name)) {
+            let value = &trimmed[key_name.len() + 1..];
+            return Some(parse_value(value));
+        }
+    }
+    None
+}
+
+fn parse_value(value: &str) -> String {
+    let value = value.trim();
+    if ((value.starts_with('"') && value.ends_with('"'))
+        || (value.starts_with('\'') && value.ends_with('\'')))
+        && value.len() >= 2
+    {
+        return value[1..value.len() - 1].to_string();
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_private_key_up_to_root() {
+        let temp = TempDir::new().unwrap();
+        let top = temp.path().canonicalize().unwrap();
+        let service = top.join("services").join("api");
+        fs::create_dir_all(&service).unwrap();
+        let env_file = service.join(".env");
+        let shared_keys = top.join(KEYS_FILE);
+        fs::write(&shared_keys, "DOTENV_PRIVATE_KEY_CI=\"abc\"\n").unwrap();
+
+        assert!(find_private_key(&env_file, None, "DOTENV_PRIVATE_KEY_CI", None).is_err());
+        assert_eq!(
+            find_private_key(&env_file, None, "DOTENV_PRIVATE_KEY_CI", Some(&top)).unwrap(),
+            ("abc".to_string(), KeySource::File(shared_keys))
+        );
+
+        // The nearest keys file wins
+        let local_keys = service.join(KEYS_FILE);
+        fs::write(&local_keys, "DOTENV_PRIVATE_KEY_CI=def\n").unwrap();
+        assert_eq!(
+            find_private_key(&env_file, None, "DOTENV_PRIVATE_KEY_CI", Some(&top)).unwrap(),
+            ("def".to_string(), KeySource::File(local_keys))
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use crate::parser::document::{Document, Line};
+use crate::parser::{Diagnostic, DotenvParser, Quote, Severity};
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::{read_file, write_file};
+use clap::ValueEnum;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::info;
+
+/// Key patterns whose values are expected to be encrypted
+pub const SENSITIVE_PATTERNS: &[&str] = &["*_SECRET", "*_TOKEN", "*_PASSWORD"];
+
+/// How a lint rule is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RuleLevel {
+    /// The rule is not checked
+    Off,
+    /// Violations are reported but do not fail the lint
+    Warning,
+    /// Violations fail the lint
+    Error,
+}
+
+/// Output format for lint results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LintFormat {
+    /// Source excerpts with markers, for people
+    #[default]
+    Human,
+    /// A JSON array of diagnostics, for CI annotations
+    Json,
+}
+
+/// A lint rule and its defaults
+#[derive(Debug, Clone, Copy)]
+pub struct Rule {
+    /// Name used in output and in `--rule NAME=LEVEL`
+    pub name: &'static str,
+    /// Level used unless overridden
+    pub default: RuleLevel,
+    /// Whether `--fix` can repair violations automatically
+    pub fixable: bool,
+}
+
+/// Every lint rule, in the order fixes are applied
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "syntax",
+        default: RuleLevel::Error,
+        fixable: false,
+    },
+    Rule {
+        name: "invalid-key",
+        default: RuleLevel::Error,
+        fixable: false,
+    },
+    Rule {
+        name: "duplicate-key",
+        default: RuleLevel::Error,
+        fixable: true,
+    },
+    Rule {
+        name: "lowercase-key",
+        default: RuleLevel::Warning,
+        fixable: false,
+    },
+    Rule {
+        name: "spaces-around-equals",
+        default: RuleLevel::Warning,
+        fixable: true,
+    },
+    Rule {
+        name: "unquoted-value",
+        default: RuleLevel::Warning,
+        fixable: true,
+    },
+    Rule {
+        name: "trailing-whitespace",
+        default: RuleLevel::Warning,
+        fixable: true,
+    },
+    Rule {
+        name: "final-newline",
+        default: RuleLevel::Warning,
+        fixable: true,
+    },
+    Rule {
+        name: "plaintext-secret",
+        default: RuleLevel::Warning,
+        fixable: false,
+    },
+    Rule {
+        name: "key-order",
+        default: RuleLevel::Off,
+        fixable: true,
+    },
+];
+
+/// Rule levels, starting from each rule's default
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, RuleLevel>,
+}
+
+impl LintConfig {
+    /// Override a rule level from a `NAME=LEVEL` string, e.g. `key-order=error`
+    pub fn set(&mut self, spec: &str) -> Result<()> {
+        let (name, level) = spec.split_once('=').ok_or_else(|| {
+            DotenvxError::Other(format!("invalid rule '{}': expected NAME=LEVEL", spec))
+        })?;
+        let rule = find_rule(name.trim())?;
+        let level = RuleLevel::from_str(level.trim(), true).map_err(|_| {
+            DotenvxError::Other(format!(
+                "invalid level '{}' for rule '{}': expected off, warning or error",
+                level, name
+            ))
+        })?;
+
+        self.levels.insert(rule.name, level);
+        Ok(())
+    }
+
+    /// The level in effect for a rule
+    pub fn level(&self, name: &str) -> RuleLevel {
+        self.levels
+            .get(name)
+            .copied()
+            .or_else(|| find_rule(name).ok().map(|rule| rule.default))
+            .unwrap_or(RuleLevel::Off)
+    }
+
+    fn enabled(&self, name: &str) -> bool {
+        self.level(name) != RuleLevel::Off
+    }
+}
+
+fn find_rule(name: &str) -> Result<&'static Rule> {
+    RULES.iter().find(|rule| rule.name == name).ok_or_else(|| {
+        DotenvxError::Other(format!(
+            "unknown lint rule '{}' (expected one of: {})",
+            name,
+            RULES.iter().map(|r| r.name).collect::<Vec<_>>().join(", ")
+        ))
+    })
+}
+
+/// Whether a key looks like it holds a secret
+pub fn is_sensitive_key(key: &str) -> bool {
+    SENSITIVE_PATTERNS
+        .iter()
+        .filter_map(|pattern| Pattern::new(pattern).ok())
+        .any(|pattern| pattern.matches(key))
+}
+
+/// Lint .env content
+///
+/// # Arguments
+///
+/// * `content` - The content of the .env file
+/// * `path` - Path used in diagnostics, if any
+/// * `config` - Rule levels
+///
+/// # Returns
+///
+/// Every violation of an enabled rule, in line order
+pub fn lint(content: &str, path: Option<&Path>, config: &LintConfig) -> Vec<Diagnostic> {
+    let document = Document::parse(content);
+    let mut found = Vec::new();
+
+    // Syntax, key names and duplicates come straight from the parser
+    let mut parser = DotenvParser::new();
+    let _ = parser.parse(content);
+    found.extend(parser.diagnostics().iter().cloned());
+
+    let mut previous_key: Option<&str> = None;
+    for (i, line) in document.lines.iter().enumerate() {
+        let line_num = i + 1;
+        let raw = line.to_string();
+
+        let trimmed_len = raw.trim_end().len();
+        if trimmed_len < raw.len() {
+            found.push(violation(
+                "trailing-whitespace",
+                line_num,
+                &raw,
+                (trimmed_len, raw.len() - trimmed_len),
+                "Trailing whitespace",
+            ));
+        }
+
+        let Line::Entry(entry) = line else {
+            previous_key = None;
+            continue;
+        };
+        let key_offset = entry.prefix.len();
+        let key_span = (key_offset, entry.key.chars().count());
+
+        if entry.key.chars().any(|c| c.is_lowercase()) {
+            found.push(violation(
+                "lowercase-key",
+                line_num,
+                &raw,
+                key_span,
+                &format!("Key '{}' should be uppercase", entry.key),
+            ));
+        }
+
+        if !entry.before_eq.is_empty() || !entry.after_eq.is_empty() {
+            let offset = key_offset + entry.key.len();
+            let length = entry.before_eq.len() + 1 + entry.after_eq.len();
+            found.push(violation(
+                "spaces-around-equals",
+                line_num,
+                &raw,
+                (offset, length),
+                "Spaces around '='",
+            ));
+        }
+
+        let value_offset = raw.len() - entry.trailing.len() - entry.raw_value.len();
+        let value_span = (value_offset, entry.raw_value.chars().count());
+        let (value, quote) = entry.value();
+        if quote == Quote::None && value.contains(|c: char| c.is_whitespace() || c == '#') {
+            found.push(violation(
+                "unquoted-value",
+                line_num,
+                &raw,
+                value_span,
+                "Value containing spaces or '#' should be quoted",
+            ));
+        }
+
+        if is_sensitive_key(&entry.key) && !value.is_empty() && !value.starts_with("encrypted:") {
+            found.push(violation(
+                "plaintext-secret",
+                line_num,
+                &raw,
+                value_span,
+                &format!(
+                    "'{}' looks sensitive but is stored in plain text; run `dotenvx encrypt`",
+                    entry.key
+                ),
+            ));
+        }
+
+        if let Some(previous) = previous_key {
+            if entry.key.as_str() < previous {
+                found.push(violation(
+                    "key-order",
+                    line_num,
+                    &raw,
+                    key_span,
+                    &format!("Key '{}' should come before '{}'", entry.key, previous),
+                ));
+            }
+        }
+        previous_key = Some(&entry.key);
+    }
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        let last = document
+            .lines
+            .last()
+            .map(Line::to_string)
+            .unwrap_or_default();
+        found.push(violation(
+            "final-newline",
+            document.lines.len(),
+            &last,
+            (last.len(), 1),
+            "Missing newline at end of file",
+        ));
+    }
+
+    // Apply configured levels, dropping disabled rules
+    let mut diagnostics: Vec<Diagnostic> = found
+        .into_iter()
+        .filter_map(|mut diagnostic| {
+            let code = diagnostic.code.clone().unwrap_or_default();
+            diagnostic.severity = match config.level(&code) {
+                RuleLevel::Off => return None,
+                RuleLevel::Warning => Severity::Warning,
+                RuleLevel::Error => Severity::Error,
+            };
+            diagnostic.path = path.map(Path::to_path_buf);
+            Some(diagnostic)
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+fn violation(
+    code: &str,
+    line: usize,
+    raw: &str,
+    (offset, length): (usize, usize),
+    message: &str,
+) -> Diagnostic {
+    let column = raw[..offset.min(raw.len())].chars().count() + 1;
+    let mut diagnostic = Diagnostic::warning(line, column, length, message, raw);
+    diagnostic.code = Some(code.to_string());
+    diagnostic
+}
+
+/// Apply automatic fixes for every enabled, fixable rule
+///
+/// # Arguments
+///
+/// * `content` - The content of the .env file
+/// * `config` - Rule levels; disabled rules are not fixed
+///
+/// # Returns
+///
+/// The fixed content
+pub fn fix(content: &str, config: &LintConfig) -> String {
+    let mut document = Document::parse(content);
+
+    if config.enabled("duplicate-key") {
+        // The last assignment wins when loading, so earlier ones can go
+        let mut seen = std::collections::HashSet::new();
+        let mut keep = vec![true; document.lines.len()];
+        for (i, line) in document.lines.iter().enumerate().rev() {
+            if let Line::Entry(entry) = line {
+                keep[i] = seen.insert(entry.key.clone());
+            }
+        }
+        let mut keep = keep.into_iter();
+        document.lines.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    for entry in document.entries_mut() {
+        if config.enabled("spaces-around-equals") {
+            entry.before_eq.clear();
+            entry.after_eq.clear();
+        }
+
+        let (value, quote) = entry.value();
+        if config.enabled("unquoted-value")
+            && quote == Quote::None
+            && value.contains(|c: char| c.is_whitespace() || c == '#')
+        {
+            entry.raw_value = format!(
+                "\"{}\"",
+                entry.raw_value.replace('\\', "\\\\").replace('"', "\\\"")
+            );
+        }
+    }
+
+    if config.enabled("trailing-whitespace") {
+        for line in document.lines.iter_mut() {
+            match line {
+                Line::Entry(entry) => entry.trailing.clear(),
+                Line::Blank(raw) | Line::Comment(raw) | Line::Invalid(raw) => {
+                    raw.truncate(raw.trim_end().len())
+                }
+            }
+        }
+    }
+
+    if config.enabled("key-order") {
+        document.sort_sections();
+    }
+
+    if config.enabled("final-newline") && !document.lines.is_empty() {
+        document.final_newline = true;
+    }
+
+    document.to_string()
+}
+
+/// Lint a .env file, optionally fixing it in place first
+///
+/// # Arguments
+///
+/// * `env_file` - Path to the .env file
+/// * `config` - Rule levels
+/// * `apply_fixes` - Rewrite the file with automatic fixes before reporting
+///
+/// # Returns
+///
+/// The remaining diagnostics
+pub fn lint_file(
+    env_file: &Path,
+    config: &LintConfig,
+    apply_fixes: bool,
+) -> Result<Vec<Diagnostic>> {
+    let mut content = read_file(env_file)?;
+
+    if apply_fixes {
+        let fixed = fix(&content, config);
+        if fixed != content {
+            write_file(env_file, &fixed)?;
+            info!("✔ fixed {}", env_file.display());
+            content = fixed;
+        }
+    }
+
+    Ok(lint(&content, Some(env_file), config))
+}
+
+/// Render diagnostics as a JSON array
+pub fn render_json(diagnostics: &[Diagnostic]) -> String {
+    let items: Vec<serde_json::Value> = diagnostics
+        .iter()
+        .map(|d| {
+            let rule = d.code.as_deref().unwrap_or_default();
+            serde_json::json!({
+                "path": d.path.as_ref().map(|p| p.display().to_string()),
+                "line": d.line,
+                "column": d.column,
+                "length": d.length,
+                "severity": d.severity.to_string(),
+                "rule": rule,
+                "message": d.message,
+                "fixable": find_rule(rule).map(|r| r.fixable).unwrap_or(false),
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&items).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .map(|d| d.code.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_file() {
+        let content = "# Database\nDB_HOST=localhost\nDB_PASSWORD=\"encrypted:abc\"\n";
+        assert!(lint(content, None, &LintConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_rules() {
+        let content = "lower=1\nKEY = 2\nMSG=hello world\nEND=x  \nAPI_TOKEN=abc\nKEY=3";
+        let diagnostics = lint(content, None, &LintConfig::default());
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                "lowercase-key",
+                "spaces-around-equals",
+                "unquoted-value",
+                "trailing-whitespace",
+                "plaintext-secret",
+                "duplicate-key",
+                "final-newline",
+            ]
+        );
+        assert_eq!(diagnostics[5].severity, Severity::Error);
+        assert_eq!((diagnostics[2].line, diagnostics[2].column), (3, 5));
+    }
+
+    #[test]
+    fn test_config_levels() {
+        let mut config = LintConfig::default();
+        config.set("key-order=error").unwrap();
+        config.set("lowercase-key=off").unwrap();
+        assert!(config.set("no-such-rule=error").is_err());
+        assert!(config.set("key-order=loud").is_err());
+
+        let diagnostics = lint("b=1\nA=2\n", None, &config);
+        assert_eq!(codes(&diagnostics), vec!["key-order"]);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_fix() {
+        let mut config = LintConfig::default();
+        config.set("key-order=warning").unwrap();
+
+        let content = "# section\nZ = 1  \nA=a # b\nA=\"x\\\\y\" z\n\nC=1\nB=2";
+        let fixed = fix(content, &config);
+        assert_eq!(
+            fixed,
+            "# section\nA=\"\\\"x\\\\\\\\y\\\" z\"\nZ=1\n\nB=2\nC=1\n"
+        );
+        assert!(lint(&fixed, None, &config).is_empty());
+    }
+
+    #[test]
+    fn test_fix_preserves_values() {
+        let content = "MSG=a \\n b # c\nPATH_LIKE=C:\\dir x\n";
+        let fixed = fix(content, &LintConfig::default());
+
+        let mut before = DotenvParser::new();
+        before.parse(content).unwrap();
+        let mut after = DotenvParser::new();
+        after.parse(&fixed).unwrap();
+        assert_eq!(before.variables(), after.variables());
+    }
+
+    #[test]
+    fn test_render_json() {
+        let diagnostics = lint("a=1", Some(Path::new(".env")), &LintConfig::default());
+        let json: serde_json::Value = serde_json::from_str(&render_json(&diagnostics)).unwrap();
+        assert_eq!(json[0]["rule"], "lowercase-key");
+        assert_eq!(json[0]["path"], ".env");
+        assert_eq!(json[0]["fixable"], false);
+        assert_eq!(json[1]["rule"], "final-newline");
+        assert_eq!(json[1]["fixable"], true);
+    }
+}
@@ -0,0 +1,599 @@
+use crate::crypto::decrypt;
+use crate::parser::{
+    is_valid_key, log_warnings, CommandContext, DotenvParser, Quote, SubstitutionPolicy,
+};
+use crate::services::keys::{find_private_key, keys_files, KeySource};
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Which source wins when a `${VAR}` reference is defined both in the loaded
+/// files and in the process environment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExpandPrecedence {
+    /// Values from the .env files shadow the process environment
+    #[default]
+    File,
+    /// Values from the process environment shadow the .env files
+    Env,
+}
+
+/// Variables loaded by [`EnvLoader::load_detailed`]
+#[derive(Debug, Clone, Default)]
+pub struct LoadedEnv {
+    /// The merged variables, as returned by [`EnvLoader::load`]
+    pub variables: HashMap<String, String>,
+    /// Every assignment of each variable, from lowest to highest precedence,
+    /// so the last one is the value in `variables`
+    pub provenance: HashMap<String, Vec<Provenance>>,
+    /// Each loaded file, in load order, with where its private key was found
+    pub files: Vec<(PathBuf, Option<KeySource>)>,
+}
+
+impl LoadedEnv {
+    /// Keys and values of every assignment that was stored encrypted,
+    /// including ones a later assignment overrode
+    pub fn secrets(&self) -> Vec<(String, String)> {
+        let mut secrets: Vec<(String, String)> = self
+            .provenance
+            .iter()
+            .flat_map(|(key, assignments)| {
+                assignments
+                    .iter()
+                    .filter(|a| a.transforms.contains(&Transform::Decrypted))
+                    .map(move |a| (key.clone(), a.value.clone()))
+            })
+            .collect();
+        secrets.sort();
+        secrets
+    }
+
+    /// Keys whose value is the text of a command that was not run
+    pub fn unevaluated(&self) -> HashSet<String> {
+        self.provenance
+            .iter()
+            .filter(|(key, assignments)| {
+                self.variables.contains_key(*key)
+                    && assignments
+                        .last()
+                        .is_some_and(|a| a.transforms.contains(&Transform::Unevaluated))
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+/// One assignment of a variable, as reported by `explain`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Where the assignment was made
+    pub source: Source,
+    /// The value once every transformation was applied
+    pub value: String,
+    /// What happened to the value after it was read, in order
+    pub transforms: Vec<Transform>,
+}
+
+/// Where a variable was assigned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A line of an .env file
+    File { path: PathBuf, line: usize },
+    /// The environment dotenvx was started with
+    Process,
+    /// A `-e KEY=value` argument
+    Inline,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File { path, line } => write!(f, "{}:{}", path.display(), line),
+            Self::Process => write!(f, "process environment"),
+            Self::Inline => write!(f, "inline -e"),
+        }
+    }
+}
+
+/// A change made to a value after it was read from a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Decrypted from `encrypted:` ciphertext
+    Decrypted,
+    /// Replaced the output of `$(...)` or a backtick command
+    Substituted,
+    /// Resolved `${VAR}` references
+    Expanded,
+    /// Kept the text of a command substitution, which was not run
+    Unevaluated,
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Decrypted => "decrypted",
+            Self::Substituted => "substituted",
+            Self::Expanded => "expanded",
+            Self::Unevaluated => "command not run",
+        })
+    }
+}
+
+/// Loads .env files and resolves variable references across them
+///
+/// References are resolved against a chain of layers, from lowest to highest
+/// precedence: the process environment, the files loaded before the current
+/// one, the current file, and inline values. With [`ExpandPrecedence::Env`]
+/// the process environment moves above the files, but stays below inline
+/// values.
+#[derive(Debug, Default)]
+pub struct EnvLoader<'a> {
+    keys_file: Option<&'a Path>,
+    root: Option<&'a Path>,
+    precedence: ExpandPrecedence,
+    process_env: HashMap<String, String>,
+    inline: HashMap<String, String>,
+    substitution: SubstitutionPolicy,
+    strict: bool,
+}
+
+impl<'a> EnvLoader<'a> {
+    /// Create a loader that resolves references against the current process environment
+    pub fn new() -> Self {
+        Self {
+            process_env: std::env::vars().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Use a specific .env.keys file for decryption
+    pub fn keys_file(mut self, keys_file: Option<&'a Path>) -> Self {
+        self.keys_file = keys_file;
+        self
+    }
+
+    /// Also search .env.keys files in the directories above each file, up to `root`
+    pub fn root(mut self, root: Option<&'a Path>) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// Set whether the files or the process environment win during expansion
+    pub fn precedence(mut self, precedence: ExpandPrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Replace the process environment used for expansion
+    pub fn process_env(mut self, process_env: HashMap<String, String>) -> Self {
+        self.process_env = process_env;
+        self
+    }
+
+    /// Reject invalid variable names and duplicate keys
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the policy for executing command substitutions
+    pub fn substitution(mut self, policy: SubstitutionPolicy) -> Self {
+        self.substitution = policy;
+        self
+    }
+
+    /// Set inline values, which take precedence over every other layer
+    pub fn inline(mut self, inline: HashMap<String, String>) -> Self {
+        self.inline = inline;
+        self
+    }
+
+    /// The inline values set with [`EnvLoader::inline`]
+    pub fn inline_values(&self) -> &HashMap<String, String> {
+        &self.inline
+    }
+
+    /// Parse `KEY=value` assignments given on the command line
+    ///
+    /// Each assignment uses the same quoting rules as a .env file and is
+    /// evaluated like one, except that references resolve only against the
+    /// other assignments and the process environment, never the loaded files.
+    ///
+    /// # Arguments
+    ///
+    /// * `assignments` - One `KEY=value` per item
+    ///
+    /// # Returns
+    ///
+    /// The inline values, ready to pass to [`EnvLoader::inline`]
+    pub fn parse_inline(&self, assignments: &[String]) -> Result<HashMap<String, String>> {
+        let mut parser = DotenvParser::new();
+        for assignment in assignments {
+            let invalid = |reason: &str| {
+                DotenvxError::Other(format!(
+                    "invalid inline variable '{}': {}",
+                    assignment, reason
+                ))
+            };
+
+            let (key, value) = assignment
+                .split_once('=')
+                .ok_or_else(|| invalid("expected KEY=value"))?;
+            let key = key.trim();
+            if !is_valid_key(key) {
+                return Err(invalid("not a valid variable name"));
+            }
+
+            let mut single = DotenvParser::new().strict(true);
+            single
+                .parse(assignment)
+                .map_err(|_| invalid("expected KEY=value"))?;
+            if single.entries().len() != 1 {
+                return Err(invalid("expected a single KEY=value"));
+            }
+            if single.entries()[0].quote == Quote::None
+                && value.trim_start().starts_with(['"', '\'', '`'])
+            {
+                return Err(invalid("unterminated quote"));
+            }
+        }
+        parser.parse(&assignments.join("\n"))?;
+
+        let context = CommandContext::resolve(&self.substitution, None, parser.variables());
+        parser.substitute_with(&self.substitution, &context)?;
+        parser.expand_with(|_| None, |name| self.process_env.get(name).cloned())?;
+
+        Ok(parser.variables().clone())
+    }
+
+    /// The files whose contents affect what [`EnvLoader::load`] returns
+    ///
+    /// These are the .env files and the .env.keys files that may hold their
+    /// private keys, whether or not they exist yet.
+    pub fn sources(&self, env_files: &[&Path]) -> Vec<PathBuf> {
+        let mut sources: Vec<PathBuf> = env_files.iter().map(|f| f.to_path_buf()).collect();
+        let keys_files = env_files
+            .iter()
+            .flat_map(|f| keys_files(f, self.keys_file, self.root));
+        for keys_file in keys_files {
+            if !sources.contains(&keys_file) {
+                sources.push(keys_file);
+            }
+        }
+        sources
+    }
+
+    /// Load and merge the given files, later files overriding earlier ones
+    ///
+    /// # Arguments
+    ///
+    /// * `env_files` - Paths to .env files to load, in order
+    ///
+    /// # Returns
+    ///
+    /// The merged variables, without the process environment or inline values
+    pub fn load(&self, env_files: &[&Path]) -> Result<HashMap<String, String>> {
+        self.load_detailed(env_files).map(|loaded| loaded.variables)
+    }
+
+    /// Like [`EnvLoader::load`], also reporting which values were decrypted
+    pub fn load_detailed(&self, env_files: &[&Path]) -> Result<LoadedEnv> {
+        let mut loaded = LoadedEnv::default();
+
+        for env_file in env_files {
+            debug!("Loading env file: {}", env_file.display());
+            self.load_file(env_file, &mut loaded)?;
+        }
+
+        Ok(loaded)
+    }
+
+    fn load_file(&self, env_file: &Path, loaded: &mut LoadedEnv) -> Result<()> {
+        let earlier = &loaded.variables;
+        let content = read_file(env_file)?;
+        let mut parser = DotenvParser::with_path(env_file).strict(self.strict);
+        parser.parse(&content)?;
+        log_warnings(parser.diagnostics());
+
+        // Decrypt first, so substitution and expansion can build on the plaintext
+        let mut transforms = vec![Vec::new(); parser.entries().len()];
+        let private_key =
+            find_private_key(env_file, self.keys_file, "DOTENV_PRIVATE_KEY", self.root).ok();
+        if let Some((private_key, _)) = &private_key {
+            for (index, transforms) in transforms.iter_mut().enumerate() {
+                let entry = &parser.entries()[index];
+                if !entry.encrypted {
+                    continue;
+                }
+
+                match decrypt(&entry.value, private_key) {
+                    Ok(decrypted) => {
+                        debug!("Decrypted key: {}", entry.key);
+                        transforms.push(Transform::Decrypted);
+                        parser.set_value(index, decrypted);
+                    }
+                    Err(e) => {
+                        debug!("Failed to decrypt {}: {:?}", entry.key, e);
+                        // Continue with encrypted value
+                    }
+                }
+            }
+        }
+
+        let mut visible = earlier.clone();
+        visible.extend(parser.variables().clone());
+        let context = CommandContext::resolve(&self.substitution, Some(env_file), &visible);
+        let before = entry_values(&parser);
+        parser.substitute_with(&self.substitution, &context)?;
+        record_changes(&parser, &before, &mut transforms, Transform::Substituted);
+        if !self.substitution.enabled {
+            // The command text stands in for the value it would produce
+            for (entry, transforms) in parser.entries().iter().zip(&mut transforms) {
+                if entry.has_command() {
+                    transforms.push(Transform::Unevaluated);
+                }
+            }
+        }
+
+        let before_expansion = entry_values(&parser);
+        let lookup = |layers: &[&HashMap<String, String>], name: &str| {
+            layers.iter().find_map(|layer| layer.get(name).cloned())
+        };
+        match self.precedence {
+            ExpandPrecedence::File => parser.expand_with(
+                |name| lookup(&[&self.inline], name),
+                |name| lookup(&[earlier, &self.process_env], name),
+            )?,
+            ExpandPrecedence::Env => parser.expand_with(
+                |name| lookup(&[&self.inline, &self.process_env], name),
+                |name| lookup(&[earlier], name),
+            )?,
+        }
+        record_changes(
+            &parser,
+            &before_expansion,
+            &mut transforms,
+            Transform::Expanded,
+        );
+
+        let mut variables = parser.variables().clone();
+
+        // Remove DOTENV_PUBLIC_KEY from exported variables
+        variables.remove("DOTENV_PUBLIC_KEY");
+
+        let assignments = parser
+            .entries()
+            .iter()
+            .zip(transforms)
+            .filter(|(entry, _)| entry.key != "DOTENV_PUBLIC_KEY");
+        for (entry, transforms) in assignments {
+            let provenance = Provenance {
+                source: Source::File {
+                    path: env_file.to_path_buf(),
+                    line: entry.line,
+                },
+                value: entry.value.clone(),
+                transforms,
+            };
+            loaded
+                .provenance
+                .entry(entry.key.clone())
+                .or_default()
+                .push(provenance);
+        }
+        loaded.variables.extend(variables);
+        loaded.files.push((
+            env_file.to_path_buf(),
+            private_key.map(|(_, source)| source),
+        ));
+
+        Ok(())
+    }
+}
+
+fn entry_values(parser: &DotenvParser) -> Vec<String> {
+    parser.entries().iter().map(|e| e.value.clone()).collect()
+}
+
+/// Note `transform` for every entry whose value differs from `before`
+fn record_changes(
+    parser: &DotenvParser,
+    before: &[String],
+    transforms: &mut [Vec<Transform>],
+    transform: Transform,
+) {
+    for ((entry, before), transforms) in parser.entries().iter().zip(before).zip(transforms) {
+        if entry.value != *before {
+            transforms.push(transform);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::fs::write_file;
+    use tempfile::TempDir;
+
+    fn make_env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_from_process_env() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "URL=http://${HOSTNAME}").unwrap();
+
+        let vars = EnvLoader::new()
+            .process_env(make_env(&[("HOSTNAME", "box")]))
+            .load(&[&env_file])
+            .unwrap();
+        assert_eq!(vars.get("URL").unwrap(), "http://box");
+        assert!(!vars.contains_key("HOSTNAME"));
+    }
+
+    #[test]
+    fn test_expand_from_earlier_file() {
+        let temp = TempDir::new().unwrap();
+        let base = temp.path().join(".env");
+        let local = temp.path().join(".env.local");
+        write_file(&base, "HOST=db.internal").unwrap();
+        write_file(&local, "DATABASE_URL=postgres://${HOST}/app").unwrap();
+
+        let vars = EnvLoader::new()
+            .process_env(HashMap::new())
+            .load(&[&base, &local])
+            .unwrap();
+        assert_eq!(
+            vars.get("DATABASE_URL").unwrap(),
+            "postgres://db.internal/app"
+        );
+    }
+
+    #[test]
+    fn test_expand_precedence() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "HOST=file\nURL=http://$HOST").unwrap();
+        let process_env = make_env(&[("HOST", "process")]);
+
+        let vars = EnvLoader::new()
+            .process_env(process_env.clone())
+            .load(&[&env_file])
+            .unwrap();
+        assert_eq!(vars.get("URL").unwrap(), "http://file");
+
+        let vars = EnvLoader::new()
+            .process_env(process_env)
+            .precedence(ExpandPrecedence::Env)
+            .load(&[&env_file])
+            .unwrap();
+        assert_eq!(vars.get("URL").unwrap(), "http://process");
+    }
+
+    #[test]
+    fn test_expand_with_decrypted_values() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let keys_file = temp.path().join(".env.keys");
+        let keypair = crate::crypto::Keypair::generate();
+        let password = crate::crypto::encrypt("s3cr$t", &keypair.public_key()).unwrap();
+        write_file(
+            &env_file,
+            &format!(
+                "DB_PASSWORD=\"{}\"\nDATABASE_URL=postgres://app:${{DB_PASSWORD}}@db/app",
+                password
+            ),
+        )
+        .unwrap();
+        write_file(
+            &keys_file,
+            &format!("DOTENV_PRIVATE_KEY={}", keypair.private_key()),
+        )
+        .unwrap();
+
+        let loaded = EnvLoader::new()
+            .process_env(HashMap::new())
+            .load_detailed(&[&env_file])
+            .unwrap();
+        let vars = &loaded.variables;
+        assert_eq!(vars.get("DB_PASSWORD").unwrap(), "s3cr$t");
+        assert_eq!(
+            vars.get("DATABASE_URL").unwrap(),
+            "postgres://app:s3cr$t@db/app"
+        );
+        assert_eq!(
+            loaded.secrets(),
+            [("DB_PASSWORD".to_string(), "s3cr$t".to_string())]
+        );
+        let url = &loaded.provenance["DATABASE_URL"][0];
+        assert_eq!(
+            url.source,
+            Source::File {
+                path: env_file.clone(),
+                line: 2
+            }
+        );
+        assert_eq!(url.transforms, [Transform::Expanded]);
+    }
+
+    #[test]
+    fn test_inline_values_win() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "HOST=file\nURL=http://$HOST").unwrap();
+
+        let vars = EnvLoader::new()
+            .process_env(make_env(&[("HOST", "process")]))
+            .precedence(ExpandPrecedence::Env)
+            .inline(make_env(&[("HOST", "inline")]))
+            .load(&[&env_file])
+            .unwrap();
+        assert_eq!(vars.get("URL").unwrap(), "http://inline");
+    }
+
+    #[test]
+    fn test_unevaluated_commands() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let marker = temp.path().join("marker");
+        write_file(
+            &env_file,
+            &format!("A=$(touch {})\nB=`date`\nC='$(date)'\n", marker.display()),
+        )
+        .unwrap();
+
+        let env = EnvLoader::new()
+            .substitution(SubstitutionPolicy {
+                enabled: false,
+                ..SubstitutionPolicy::default()
+            })
+            .load_detailed(&[&env_file])
+            .unwrap();
+        assert!(!marker.exists());
+        assert_eq!(env.provenance["B"][0].value, "date");
+        assert_eq!(
+            env.provenance["B"][0].transforms,
+            vec![Transform::Unevaluated]
+        );
+        let mut unevaluated: Vec<String> = env.unevaluated().into_iter().collect();
+        unevaluated.sort();
+        assert_eq!(unevaluated, ["A", "B"]);
+    }
+
+    #[test]
+    fn test_parse_inline() {
+        let loader = EnvLoader::new().process_env(make_env(&[("USER", "alice")]));
+        let assignments: Vec<String> = [
+            "HOME=/home/$USER",
+            "GREETING=\"hello\\tworld\"",
+            "LITERAL='$USER'",
+            "URL=${HOME}/app",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let inline = loader.parse_inline(&assignments).unwrap();
+        assert_eq!(inline["HOME"], "/home/alice");
+        assert_eq!(inline["GREETING"], "hello\tworld");
+        assert_eq!(inline["LITERAL"], "$USER");
+        assert_eq!(inline["URL"], "/home/alice/app");
+
+        for invalid in ["NOVALUE", "1KEY=x", "=x", "A=\"open", "A=1\nB=2"] {
+            let err = loader.parse_inline(&[invalid.to_string()]).unwrap_err();
+            assert!(
+                err.to_string().starts_with("invalid inline variable"),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -1,7 +1,39 @@
+pub mod cascade;
+pub mod config;
 pub mod decrypt;
+pub mod discover;
 pub mod encrypt;
+pub mod explain;
+pub mod export;
+pub mod fmt;
+pub mod import;
+pub mod keys;
+pub mod lint;
+pub mod load;
+pub mod printenv;
+pub mod redact;
 pub mod run;
+pub mod schema;
+pub mod secret_files;
+pub mod set;
+pub mod watch;
 
+pub use cascade::{cascade_files, resolve_cascade, Convention, DEFAULT_ENV_NAME};
+pub use config::{Config, SubstitutionConfig};
 pub use decrypt::decrypt_file;
+pub use discover::{find_root, search_path};
 pub use encrypt::encrypt_file;
-pub use run::run_command;
+pub use explain::explain;
+pub use export::{ExportOptions, ExportTarget};
+pub use fmt::{format_file, ExportStyle, FormatOptions, QuoteStyle};
+pub use import::ImportFormat;
+pub use keys::{find_private_key, KeySource};
+pub use lint::{lint_file, LintConfig, LintFormat};
+pub use load::{EnvLoader, ExpandPrecedence, LoadedEnv, Provenance, Source, Transform};
+pub use printenv::EnvFormat;
+pub use redact::{Redaction, Redactor};
+pub use run::{prepare_env, run_command, RunOptions};
+pub use schema::Schema;
+pub use secret_files::SecretFiles;
+pub use set::set_values;
+pub use watch::FileWatcher;
@@ -0,0 +1,365 @@
+use crate::parser::{format_literal_entry, is_valid_key};
+use crate::utils::error::{DotenvxError, Result};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Output format for `printenv`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EnvFormat {
+    /// `export KEY='value'` for bash and other POSIX shells
+    #[default]
+    #[value(alias = "sh")]
+    Bash,
+    /// `export KEY='value'` for zsh
+    Zsh,
+    /// `set -gx KEY 'value'` for fish
+    Fish,
+    /// `$env:KEY = 'value'` for PowerShell
+    #[value(alias = "ps1")]
+    Powershell,
+    /// `$env.KEY = r#'value'#` for nushell
+    #[value(alias = "nu")]
+    Nushell,
+    /// `set KEY=value` for cmd.exe batch files
+    Cmd,
+    /// A JSON object
+    Json,
+    /// A .env file
+    Dotenv,
+    /// `KEY=value` lines for `docker run --env-file`
+    Docker,
+    /// A systemd `EnvironmentFile=`
+    Systemd,
+    /// A YAML mapping
+    Yaml,
+}
+
+/// Render variables in the given format, sorted by key
+///
+/// Every format quotes values so that the consuming shell or tool reads them
+/// back literally, without expanding or executing anything. Formats that
+/// cannot represent a value, such as a line break for `docker`, return an
+/// error rather than emitting something that would be misread.
+///
+/// # Arguments
+///
+/// * `env_vars` - Variables to render
+/// * `format` - Output format
+///
+/// # Returns
+///
+/// The rendered output, ending with a newline
+pub fn render(env_vars: &HashMap<String, String>, format: EnvFormat) -> Result<String> {
+    let sorted: BTreeMap<&String, &String> = env_vars.iter().collect();
+
+    match format {
+        EnvFormat::Json => {
+            let json = serde_json::to_string_pretty(&sorted)
+                .map_err(|e| DotenvxError::Serialize(e.to_string()))?;
+            return Ok(json + "\n");
+        }
+        EnvFormat::Yaml => {
+            let mut output = String::new();
+            for (key, value) in &sorted {
+                // JSON strings are valid double-quoted YAML scalars
+                let _ = writeln!(output, "{}: {}", json_string(key), json_string(value));
+            }
+            return Ok(output);
+        }
+        _ => {}
+    }
+
+    let mut output = String::new();
+    for (key, value) in sorted {
+        if !is_valid_key(key) {
+            return Err(DotenvxError::InvalidValue {
+                key: key.clone(),
+                message: "not a valid variable name".to_string(),
+            });
+        }
+
+        let line = match format {
+            EnvFormat::Bash | EnvFormat::Zsh => format!("export {}={}", key, posix_quote(value)),
+            EnvFormat::Fish => format!("set -gx {} {}", key, fish_quote(value)),
+            EnvFormat::Powershell => format!("$env:{} = {}", key, powershell_quote(value)),
+            EnvFormat::Nushell => format!("$env.{} = {}", key, nushell_quote(value)),
+            EnvFormat::Cmd => format!("set {}={}", key, cmd_escape(key, value)?),
+            EnvFormat::Dotenv => format_literal_entry(key, value),
+            EnvFormat::Docker => format!("{}={}", key, single_line(key, value, "docker")?),
+            EnvFormat::Systemd => format!("{}={}", key, systemd_quote(value)),
+            EnvFormat::Json | EnvFormat::Yaml => unreachable!("handled above"),
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Single quotes, with `'` written as `'\''`
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Single quotes, where fish only interprets `\\` and `\'`
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Verbatim single quotes, doubling every character PowerShell treats as a single quote
+fn powershell_quote(value: &str) -> String {
+    let mut quoted = String::from("'");
+    for ch in value.chars() {
+        if matches!(ch, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(ch);
+        }
+        quoted.push(ch);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// A raw string with more `#`s than any `'#...` run inside the value
+fn nushell_quote(value: &str) -> String {
+    let longest = value
+        .split('\'')
+        .skip(1)
+        .map(|rest| rest.chars().take_while(|&c| c == '#').count())
+        .max()
+        .unwrap_or(0);
+    let hashes = "#".repeat(longest + 1);
+    format!("r{}'{}'{}", hashes, value, hashes)
+}
+
+/// Unquoted, with cmd metacharacters caret-escaped and `%` doubled
+///
+/// Intended for batch files run with delayed expansion disabled.
+fn cmd_escape(key: &str, value: &str) -> Result<String> {
+    let value = single_line(key, value, "cmd")?;
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '%' => escaped.push_str("%%"),
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' | '"' => {
+                escaped.push('^');
+                escaped.push(ch);
+            }
+            _ => escaped.push(ch),
+        }
+    }
+    Ok(escaped)
+}
+
+/// Double quotes, escaping what systemd unescapes inside them
+fn systemd_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in value.chars() {
+        if matches!(ch, '\\' | '"' | '`' | '$') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn single_line<'v>(key: &str, value: &'v str, format: &str) -> Result<&'v str> {
+    if value.contains(['\n', '\r']) {
+        return Err(DotenvxError::InvalidValue {
+            key: key.to_string(),
+            message: format!("line breaks cannot be represented in the {} format", format),
+        });
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::path::Path;
+    #[cfg(unix)]
+    use std::process::Command;
+
+    const NASTY: &str = "a'b\"c$(touch pwned)`id`\\n\\\\ %PATH% ^&|<>() #!\u{2019}'#x";
+
+    fn vars(value: &str) -> HashMap<String, String> {
+        [("KEY".to_string(), value.to_string())].into()
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let cases = [
+            (EnvFormat::Bash, "export KEY='it'\\''s'\n"),
+            (EnvFormat::Zsh, "export KEY='it'\\''s'\n"),
+            (EnvFormat::Fish, "set -gx KEY 'it\\'s'\n"),
+            (EnvFormat::Powershell, "$env:KEY = 'it''s'\n"),
+            (EnvFormat::Nushell, "$env.KEY = r#'it's'#\n"),
+            (EnvFormat::Cmd, "set KEY=it's\n"),
+            (EnvFormat::Json, "{\n  \"KEY\": \"it's\"\n}\n"),
+            (EnvFormat::Dotenv, "KEY=\"it's\"\n"),
+            (EnvFormat::Docker, "KEY=it's\n"),
+            (EnvFormat::Systemd, "KEY=\"it's\"\n"),
+            (EnvFormat::Yaml, "\"KEY\": \"it's\"\n"),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(
+                render(&vars("it's"), format).unwrap(),
+                expected,
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    /// Run `script` with a shell, or return `None` if it is not installed
+    #[cfg(unix)]
+    fn run_shell(program: &str, args: &[&str], script: &str, dir: &Path) -> Option<String> {
+        let output = match Command::new(program)
+            .args(args)
+            .arg(script)
+            .current_dir(dir)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => panic!("{}: {}", program, e),
+        };
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(String::from_utf8(output.stdout).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shells_are_injection_safe() {
+        let value = format!("{}\nsecond line", NASTY);
+        let shells: [(EnvFormat, &str, &[&str], &str); 6] = [
+            (EnvFormat::Bash, "bash", &["-c"], "printf %s \"$KEY\""),
+            (EnvFormat::Bash, "sh", &["-c"], "printf %s \"$KEY\""),
+            (EnvFormat::Zsh, "zsh", &["-c"], "printf %s \"$KEY\""),
+            (EnvFormat::Fish, "fish", &["-c"], "printf %s \"$KEY\""),
+            (
+                EnvFormat::Powershell,
+                "pwsh",
+                &["-NoProfile", "-NonInteractive", "-Command"],
+                "[Console]::Out.Write($env:KEY)",
+            ),
+            (EnvFormat::Nushell, "nu", &["-c"], "print -n $env.KEY"),
+        ];
+
+        for (format, program, args, print) in shells {
+            let temp = tempfile::TempDir::new().unwrap();
+            let script = format!("{}{}", render(&vars(&value), format).unwrap(), print);
+            let Some(output) = run_shell(program, args, &script, temp.path()) else {
+                eprintln!("skipping {}: not installed", program);
+                continue;
+            };
+            assert_eq!(output, value, "{}", program);
+            assert!(!temp.path().join("pwned").exists(), "{}", program);
+        }
+    }
+
+    /// Undo caret escapes and `%%` as cmd.exe does when running a batch file
+    fn cmd_decode(line: &str) -> String {
+        let mut decoded = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '^' => decoded.extend(chars.next()),
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    decoded.push('%');
+                }
+                '%' => panic!("unescaped % would expand: {}", line),
+                '&' | '|' | '<' | '>' | '(' | ')' | '"' => {
+                    panic!("unescaped {} is a metacharacter: {}", ch, line)
+                }
+                _ => decoded.push(ch),
+            }
+        }
+        decoded
+    }
+
+    /// Unquote a double-quoted systemd `EnvironmentFile=` value
+    fn systemd_decode(value: &str) -> String {
+        let inner = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap();
+        let mut decoded = String::new();
+        let mut chars = inner.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => decoded.extend(chars.next()),
+                '"' => panic!("unescaped quote ends the value early: {}", value),
+                _ => decoded.push(ch),
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn test_decoders_round_trip() {
+        let line = |format| {
+            render(&vars(NASTY), format)
+                .unwrap()
+                .trim_end_matches('\n')
+                .to_string()
+        };
+
+        let cmd = line(EnvFormat::Cmd);
+        assert_eq!(cmd_decode(cmd.strip_prefix("set KEY=").unwrap()), NASTY);
+
+        let docker = line(EnvFormat::Docker);
+        assert_eq!(docker.strip_prefix("KEY=").unwrap(), NASTY);
+
+        let value = format!("{}\nsecond line", NASTY);
+        let systemd = render(&vars(&value), EnvFormat::Systemd).unwrap();
+        let systemd = systemd.trim_end_matches('\n').strip_prefix("KEY=").unwrap();
+        assert_eq!(systemd_decode(systemd), value);
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(fish_quote(r"a\'b"), r"'a\\\'b'");
+        assert_eq!(powershell_quote("a'b\u{2019}c"), "'a''b\u{2019}\u{2019}c'");
+        assert_eq!(nushell_quote("a'#b"), "r##'a'#b'##");
+        assert_eq!(nushell_quote("plain"), "r#'plain'#");
+        assert_eq!(
+            cmd_escape("KEY", "50% & \"x\" | y").unwrap(),
+            "50%% ^& ^\"x^\" ^| y"
+        );
+        assert_eq!(systemd_quote("a\"$b`\\"), "\"a\\\"\\$b\\`\\\\\"");
+
+        let yaml = render(&vars(NASTY), EnvFormat::Yaml).unwrap();
+        let parsed: serde_json::Value =
+            serde_json::from_str(yaml.trim().strip_prefix("\"KEY\": ").unwrap()).unwrap();
+        assert_eq!(parsed, NASTY);
+
+        let dotenv = render(&vars(NASTY), EnvFormat::Dotenv).unwrap();
+        let mut parser = crate::parser::DotenvParser::new();
+        parser.parse(&dotenv).unwrap();
+        assert_eq!(parser.variables()["KEY"], NASTY);
+    }
+
+    #[test]
+    fn test_unrepresentable() {
+        assert!(render(&vars("a\nb"), EnvFormat::Docker).is_err());
+        assert!(render(&vars("a\nb"), EnvFormat::Cmd).is_err());
+        assert!(render(&vars("a\nb"), EnvFormat::Bash).is_ok());
+
+        let bad_key: HashMap<String, String> = [("A;rm -rf /".to_string(), "1".to_string())].into();
+        assert!(render(&bad_key, EnvFormat::Bash).is_err());
+        assert!(render(&bad_key, EnvFormat::Json).is_ok());
+    }
+}
@@ -0,0 +1,242 @@
+use base64::{engine::general_purpose, Engine as _};
+use clap::ValueEnum;
+use regex::bytes::Regex;
+use std::collections::HashMap;
+
+/// Secrets shorter than this are not redacted, since they would match
+/// unrelated output everywhere
+pub const MIN_SECRET_LEN: usize = 4;
+
+/// Which forms of a secret are redacted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Redaction {
+    /// The value as decrypted
+    #[default]
+    Plain,
+    /// The value, its base64 encoding and its URL encoding
+    Encoded,
+}
+
+/// Replaces secret values in output with `[REDACTED:KEY]`
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    // `None` when there is nothing to redact
+    regex: Option<Regex>,
+    replacements: HashMap<Vec<u8>, Vec<u8>>,
+    patterns: Vec<Vec<u8>>,
+}
+
+impl Redactor {
+    /// Build a redactor for the given secrets
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets` - Keys and values to redact; for a value shared by several
+    ///   keys, the first key names the replacement
+    /// * `redaction` - Which forms of each value to redact
+    pub fn new(secrets: &[(String, String)], redaction: Redaction) -> Self {
+        let mut replacements: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        for (key, value) in secrets {
+            if value.len() < MIN_SECRET_LEN {
+                continue;
+            }
+            let replacement = format!("[REDACTED:{}]", key).into_bytes();
+
+            let mut forms = vec![value.clone()];
+            if redaction == Redaction::Encoded {
+                forms.push(general_purpose::STANDARD.encode(value));
+                forms.push(general_purpose::STANDARD_NO_PAD.encode(value));
+                forms.push(url_encode(value));
+            }
+            for form in forms {
+                replacements
+                    .entry(form.into_bytes())
+                    .or_insert_with(|| replacement.clone());
+            }
+        }
+
+        // Longest first, so the alternation prefers a secret over its prefix
+        let mut patterns: Vec<Vec<u8>> = replacements.keys().cloned().collect();
+        patterns.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+        let regex = (!patterns.is_empty()).then(|| {
+            let alternation: Vec<String> = patterns
+                .iter()
+                .map(|p| regex::escape(&String::from_utf8_lossy(p)))
+                .collect();
+            Regex::new(&alternation.join("|")).expect("escaped literals form a valid regex")
+        });
+
+        Self {
+            regex,
+            replacements,
+            patterns,
+        }
+    }
+
+    /// Whether there is anything to redact
+    pub fn is_empty(&self) -> bool {
+        self.regex.is_none()
+    }
+
+    /// Start redacting a new stream
+    pub fn stream(&self) -> RedactStream<'_> {
+        RedactStream {
+            redactor: self,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Start of the longest suffix of `buffer` that could begin a secret
+    fn holdback(&self, buffer: &[u8]) -> usize {
+        let mut longest = 0;
+        for pattern in &self.patterns {
+            let max = (pattern.len() - 1).min(buffer.len());
+            if let Some(len) = (longest + 1..=max)
+                .rev()
+                .find(|&len| buffer.ends_with(&pattern[..len]))
+            {
+                longest = len;
+            }
+        }
+        buffer.len() - longest
+    }
+}
+
+/// Redacts one stream of output, such as a command's stdout
+///
+/// Secrets may be split across chunks, so bytes that could be the start of
+/// one are held back until the next chunk shows whether they are. Everything
+/// else is passed on immediately, so output is not delayed.
+#[derive(Debug)]
+pub struct RedactStream<'a> {
+    redactor: &'a Redactor,
+    pending: Vec<u8>,
+}
+
+impl RedactStream<'_> {
+    /// Redact the next chunk of the stream
+    ///
+    /// # Returns
+    ///
+    /// The output that is ready to be written
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+        let end = self.redactor.holdback(&self.pending);
+        self.drain(end)
+    }
+
+    /// Redact whatever is left at the end of the stream
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.drain(self.pending.len())
+    }
+
+    /// Redact and remove pending bytes, up to `end` unless a secret that
+    /// starts before it continues past it
+    fn drain(&mut self, end: usize) -> Vec<u8> {
+        let Some(regex) = &self.redactor.regex else {
+            return std::mem::take(&mut self.pending);
+        };
+
+        let mut output = Vec::with_capacity(end);
+        let mut position = 0;
+        for found in regex.find_iter(&self.pending) {
+            // Later matches might still grow into a longer secret
+            if found.start() >= end {
+                break;
+            }
+            output.extend_from_slice(&self.pending[position..found.start()]);
+            output.extend_from_slice(&self.redactor.replacements[found.as_bytes()]);
+            position = found.end();
+        }
+
+        let end = end.max(position);
+        output.extend_from_slice(&self.pending[position..end]);
+        self.pending.drain(..end);
+        output
+    }
+}
+
+/// Percent-encode everything but unreserved characters, as in a URL component
+fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn redact_in_chunks(redactor: &Redactor, input: &[u8], size: usize) -> String {
+        let mut stream = redactor.stream();
+        let mut output = Vec::new();
+        for chunk in input.chunks(size) {
+            output.extend(stream.push(chunk));
+        }
+        output.extend(stream.finish());
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_redacts_across_chunk_boundaries() {
+        let redactor = Redactor::new(
+            &secrets(&[("TOKEN", "abcdef"), ("SHORT", "abcd"), ("PIN", "123")]),
+            Redaction::Plain,
+        );
+        let input = b"token=abcdef, prefix=abcd, partial=abcde, pin=123\n";
+
+        for size in 1..=input.len() {
+            assert_eq!(
+                redact_in_chunks(&redactor, input, size),
+                "token=[REDACTED:TOKEN], prefix=[REDACTED:SHORT], partial=[REDACTED:SHORT]e, pin=123\n",
+                "chunk size {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_passes_output_through_promptly() {
+        let redactor = Redactor::new(&secrets(&[("TOKEN", "s3cret")]), Redaction::Plain);
+        let mut stream = redactor.stream();
+
+        // Only a possible start of the secret is held back
+        assert_eq!(stream.push(b"Password: "), b"Password: ");
+        assert_eq!(stream.push(b"hello s3c"), b"hello ");
+        assert_eq!(stream.push(b"ond\n"), b"s3cond\n");
+        assert_eq!(stream.push(b"s3c"), b"");
+        assert_eq!(stream.finish(), b"s3c");
+    }
+
+    #[test]
+    fn test_redacts_encoded_forms() {
+        let pairs = secrets(&[("PASSWORD", "p@ss w0rd!")]);
+        let plain = Redactor::new(&pairs, Redaction::Plain);
+        let encoded = Redactor::new(&pairs, Redaction::Encoded);
+        let input = b"p%40ss%20w0rd%21 cEBzcyB3MHJkIQ== p@ss w0rd!";
+
+        assert_eq!(
+            redact_in_chunks(&plain, input, 7),
+            "p%40ss%20w0rd%21 cEBzcyB3MHJkIQ== [REDACTED:PASSWORD]"
+        );
+        assert_eq!(
+            redact_in_chunks(&encoded, input, 7),
+            "[REDACTED:PASSWORD] [REDACTED:PASSWORD] [REDACTED:PASSWORD]"
+        );
+        assert!(Redactor::new(&[], Redaction::Encoded).is_empty());
+    }
+}
@@ -1,149 +1,993 @@
-use crate::crypto::decrypt;
-use crate::parser::DotenvParser;
+use crate::services::explain::explain;
+use crate::services::load::{EnvLoader, LoadedEnv, Provenance, Source};
+use crate::services::redact::{Redaction, Redactor};
+use crate::services::schema::Schema;
+use crate::services::secret_files::SecretFiles;
+use crate::services::watch::FileWatcher;
 use crate::utils::error::{DotenvxError, Result};
-use crate::utils::fs::read_file;
+use glob::Pattern;
 use std::collections::HashMap;
+use std::future::pending;
 use std::path::Path;
-use tokio::process::Command;
-use tracing::{debug, info};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, error, info, warn};
+
+/// Time a command gets to exit after a terminating signal, by default
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Prefixes of variables that control dotenvx, such as private keys, which
+/// the command does not get unless they are passed explicitly
+pub const CONTROL_PREFIXES: &[&str] = &["DOTENV_PRIVATE_KEY", "DOTENV_KEYS_", "DOTENVX_"];
+
+/// Variables a clean run still inherits from the environment
+#[cfg(unix)]
+pub const CLEAN_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LC_ALL", "TZ", "TMPDIR",
+];
+
+/// Variables a clean run still inherits from the environment
+#[cfg(not(unix))]
+pub const CLEAN_ENV: &[&str] = &[
+    "PATH",
+    "PATHEXT",
+    "SYSTEMROOT",
+    "COMSPEC",
+    "USERPROFILE",
+    "TEMP",
+    "TMP",
+];
+
+/// How `run` starts and supervises the command
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Whether .env values override variables already set in the environment
+    pub overload: bool,
+    /// Time between forwarding a terminating signal and killing the command,
+    /// or `None` to wait indefinitely
+    pub grace_period: Option<Duration>,
+    /// Whether to replace this process with the command instead of spawning
+    /// it, or `None` to replace it when that is safe
+    pub exec: Option<bool>,
+    /// Whether to restart the command when the files or keys change
+    pub watch: bool,
+    /// Schema the environment must match before the command starts
+    pub schema: Option<Schema>,
+    /// Which forms of decrypted values to hide in the command's output, or
+    /// `None` to pass the output through untouched
+    pub redact: Option<Redaction>,
+    /// Whether to inherit only [`CLEAN_ENV`] and `pass` from the environment
+    pub clean: bool,
+    /// Names or glob patterns of variables to inherit even in a clean run,
+    /// and to keep even if they control dotenvx
+    pub pass: Vec<String>,
+    /// Whether to print where each variable came from before starting
+    pub explain: bool,
+    /// Whether that report shows values instead of masking them
+    pub reveal: bool,
+    /// Names of variables to hand over as files, each replaced by `KEY_FILE`
+    pub as_files: Vec<String>,
+    /// Glob patterns selecting more variables to hand over as files
+    pub as_files_patterns: Vec<Pattern>,
+    /// The environment the command inherits from, by default this process's
+    pub process_env: HashMap<String, String>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            overload: false,
+            grace_period: Some(DEFAULT_GRACE_PERIOD),
+            exec: Some(false),
+            watch: false,
+            schema: None,
+            redact: None,
+            clean: false,
+            pass: Vec::new(),
+            explain: false,
+            reveal: false,
+            as_files: Vec::new(),
+            as_files_patterns: Vec::new(),
+            process_env: std::env::vars().collect(),
+        }
+    }
+}
+
+impl RunOptions {
+    /// The part of `process_env` the command inherits
+    ///
+    /// Control variables matching [`CONTROL_PREFIXES`] are left out unless
+    /// passed, and with `clean` so is everything but [`CLEAN_ENV`].
+    pub fn inherited_env(&self) -> HashMap<String, String> {
+        self.process_env
+            .iter()
+            .filter(|(key, _)| {
+                (!self.clean || CLEAN_ENV.contains(&key.as_str()) || self.passes(key))
+                    && !self.withholds(key)
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Whether a variable is kept from the command, wherever it was set
+    fn withholds(&self, key: &str) -> bool {
+        CONTROL_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+            && !self.passes(key)
+    }
+
+    fn passes(&self, key: &str) -> bool {
+        self.pass
+            .iter()
+            .any(|pass| pass == key || Pattern::new(pass).is_ok_and(|pattern| pattern.matches(key)))
+    }
+
+    /// Why the command must run as a child rather than replace this process
+    ///
+    /// # Returns
+    ///
+    /// The reason, or `None` if the process can be replaced
+    pub fn needs_parent(&self) -> Option<&'static str> {
+        if cfg!(not(unix)) {
+            return Some("replacing the process is only supported on Unix");
+        }
+        if self.watch {
+            return Some("--watch restarts the command");
+        }
+        if self.redact.is_some() {
+            return Some("--redact filters the command's output");
+        }
+        if self.uses_files() {
+            return Some("--as-files removes the files when the command exits");
+        }
+        None
+    }
+
+    /// Whether any variable is handed over as a file
+    fn uses_files(&self) -> bool {
+        !self.as_files.is_empty() || !self.as_files_patterns.is_empty()
+    }
+
+    /// Whether to replace this process with the command
+    fn use_exec(&self) -> Result<bool> {
+        match (self.exec, self.needs_parent()) {
+            (Some(true), Some(reason)) => Err(DotenvxError::Other(format!(
+                "--exec is unavailable: {}",
+                reason
+            ))),
+            (Some(exec), _) => Ok(exec),
+            // As PID 1 the command would have to forward signals and reap orphans itself
+            (None, reason) => Ok(reason.is_none() && !is_init()),
+        }
+    }
+}
+
+fn is_init() -> bool {
+    std::process::id() == 1
+}
 
 /// Run a command with environment variables loaded from .env files
 ///
+/// Inline values set on the loader always win. Below them, the inherited
+/// environment wins over the files unless `overload` is set. The command
+/// gets exactly this environment: variables that control dotenvx, such as
+/// `DOTENV_PRIVATE_KEY`, are removed unless passed, and a `clean` run only
+/// inherits a few basic variables.
+///
+/// When `exec` allows it, this process is replaced by the command, which
+/// then inherits its PID, stdio and signals, and this function only returns
+/// on failure. Otherwise, on Unix, the command runs in its own process
+/// group. SIGINT, SIGTERM, SIGHUP, SIGQUIT, SIGUSR1 and SIGUSR2 are forwarded
+/// to the group, and if it has not exited a grace period after a terminating
+/// signal, it is sent SIGKILL.
+///
+/// With a `schema`, the environment is checked before the command starts.
+/// With `as_files`, the selected values are written to private files, which
+/// are removed once the command has exited.
+/// With `redact`, the command's stdout and stderr are piped through this
+/// process, which replaces decrypted values with `[REDACTED:KEY]`.
+/// With `watch`, the files and their keys are reloaded when they change, and
+/// the command is stopped and started again with the new environment. If the
+/// reload or the check fails, the error is logged and the current command
+/// keeps running.
+///
 /// # Arguments
 ///
-/// * `env_files` - Paths to .env files to load
-/// * `keys_file` - Optional path to .env.keys file
+/// * `env_files` - Paths to .env files to load; missing ones are skipped,
+///   and with `watch` creating one restarts the command
+/// * `loader` - Loader configured with the keys file and evaluation options
 /// * `command` - The command to execute
 /// * `args` - Arguments for the command
-/// * `overload` - Whether to override existing environment variables
+/// * `options` - Environment precedence and supervision settings
 ///
 /// # Returns
 ///
-/// The exit code of the command
+/// The exit code of the command, or 128 plus the signal that killed it
 pub async fn run_command(
     env_files: &[&Path],
-    keys_file: Option<&Path>,
+    loader: &EnvLoader<'_>,
     command: &str,
     args: &[String],
-    overload: bool,
+    options: &RunOptions,
 ) -> Result<i32> {
     info!("Running command: {} {:?}", command, args);
 
-    // Load and merge environment variables from all files
-    let mut env_vars = HashMap::new();
+    let env = prepare_env(env_files, loader, options)?;
+    debug!("Loaded {} environment variables", env.variables.len());
+    if options.explain {
+        eprint!("{}", explain(&env, None, options.reveal)?);
+    }
 
-    for env_file in env_files {
-        debug!("Loading env file: {}", env_file.display());
-        let file_vars = load_env_file(env_file, keys_file)?;
-        env_vars.extend(file_vars);
+    #[cfg(unix)]
+    if options.use_exec()? {
+        return Err(exec(command, args, &env.variables));
     }
+    #[cfg(not(unix))]
+    options.use_exec()?;
+
+    let secret_files = options
+        .uses_files()
+        .then(|| SecretFiles::create(&options.as_files, &options.as_files_patterns))
+        .transpose()?;
+    let launch = Launch {
+        command,
+        args,
+        redact: options.redact,
+        secret_files: secret_files.as_ref(),
+    };
+    let reload = || prepare_env(env_files, loader, options);
+    let watch = options.watch.then(|| {
+        (
+            FileWatcher::new(loader.sources(env_files)),
+            &reload as Reload,
+        )
+    });
+    let status = supervise(&launch, env, options.grace_period, watch).await?;
+
+    let exit_code = exit_code(status);
+    info!("Command exited with code: {}", exit_code);
 
-    // Merge with existing environment if not overloading
-    if !overload {
-        for (key, value) in std::env::vars() {
-            env_vars.entry(key).or_insert(value);
+    Ok(exit_code)
+}
+
+/// Load the files and merge them with the inherited environment and inline
+/// values, checking the result against the schema if there is one
+///
+/// # Arguments
+///
+/// * `env_files` - Paths to .env files to load, skipping those that do not exist
+/// * `loader` - Loader configured with the keys file, evaluation options and
+///   inline values
+/// * `options` - Environment precedence, scrubbing and schema
+///
+/// # Returns
+///
+/// The command's environment, with the provenance of every variable
+pub fn prepare_env(
+    env_files: &[&Path],
+    loader: &EnvLoader<'_>,
+    options: &RunOptions,
+) -> Result<LoadedEnv> {
+    let existing: Vec<&Path> = env_files.iter().copied().filter(|f| f.exists()).collect();
+    let mut env = loader.load_detailed(&existing)?;
+
+    // Inherited variables win unless overloading, and inline values always do
+    for (key, value) in options.inherited_env() {
+        let assignments = env.provenance.entry(key).or_default();
+        let inherited = Provenance {
+            source: Source::Process,
+            value,
+            transforms: Vec::new(),
+        };
+        if options.overload {
+            assignments.insert(0, inherited);
+        } else {
+            assignments.push(inherited);
         }
     }
+    for (key, value) in loader.inline_values() {
+        env.provenance
+            .entry(key.clone())
+            .or_default()
+            .push(Provenance {
+                source: Source::Inline,
+                value: value.clone(),
+                transforms: Vec::new(),
+            });
+    }
 
-    debug!("Loaded {} environment variables", env_vars.len());
+    env.variables = env
+        .provenance
+        .iter()
+        .filter_map(|(key, assignments)| {
+            let winner = assignments.last()?;
+            (winner.source == Source::Inline || !options.withholds(key))
+                .then(|| (key.clone(), winner.value.clone()))
+        })
+        .collect();
 
-    // Execute the command
-    let mut cmd = Command::new(command);
-    cmd.args(args);
-    cmd.envs(&env_vars);
+    if let Some(schema) = &options.schema {
+        schema.check(&env.variables, &env.unevaluated())?;
+    }
+    if let Some(key) = options
+        .as_files
+        .iter()
+        .find(|key| !env.variables.contains_key(*key))
+    {
+        return Err(DotenvxError::MissingKey { key: key.clone() });
+    }
+    Ok(env)
+}
 
-    let status = cmd
-        .status()
-        .await
-        .map_err(|e| DotenvxError::CommandFailed(format!("Failed to execute command: {}", e)))?;
+/// The exit code as a shell reports it, with 128 plus the signal number for
+/// a command killed by a signal
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
 
-    let exit_code = status.code().unwrap_or(-1);
-    info!("Command exited with code: {}", exit_code);
+/// Replace this process with the command, returning only if that fails
+#[cfg(unix)]
+fn exec(command: &str, args: &[String], env_vars: &HashMap<String, String>) -> DotenvxError {
+    use std::os::unix::process::CommandExt;
 
-    Ok(exit_code)
+    // Searches PATH like execvp, with the environment passed explicitly
+    let error = std::process::Command::new(command)
+        .args(args)
+        .env_clear()
+        .envs(env_vars)
+        .exec();
+    DotenvxError::CommandFailed(format!("Failed to execute command: {}", error))
+}
+
+/// Reloads the environment after a watched file changes
+type Reload<'a> = &'a (dyn Fn() -> Result<LoadedEnv> + Sync);
+
+/// The command to start, possibly several times with different environments
+struct Launch<'a> {
+    command: &'a str,
+    args: &'a [String],
+    redact: Option<Redaction>,
+    secret_files: Option<&'a SecretFiles>,
 }
 
-fn load_env_file(env_file: &Path, keys_file: Option<&Path>) -> Result<HashMap<String, String>> {
-    let content = read_file(env_file)?;
-    let mut parser = DotenvParser::new();
-    parser.parse_with_processing(&content)?;
+impl Launch<'_> {
+    fn spawn(&self, env: &LoadedEnv) -> Result<Running> {
+        let mut cmd = Command::new(self.command);
+        cmd.args(self.args);
+        cmd.env_clear();
+        // Written only now, so a command being restarted never sees the new values
+        match self.secret_files {
+            Some(files) => cmd.envs(files.write(&env.variables)?),
+            None => cmd.envs(&env.variables),
+        };
+
+        let redactor = self
+            .redact
+            .map(|redaction| Arc::new(Redactor::new(&env.secrets(), redaction)));
+        if redactor.is_some() {
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+        }
+
+        // Signals are forwarded to the whole group, so they also reach grandchildren
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn().map_err(|e| {
+            DotenvxError::CommandFailed(format!("Failed to execute command: {}", e))
+        })?;
+
+        let mut output = Vec::new();
+        if let Some(redactor) = redactor {
+            if let Some(stdout) = child.stdout.take() {
+                output.push(tokio::spawn(redact_output(
+                    stdout,
+                    tokio::io::stdout(),
+                    Arc::clone(&redactor),
+                )));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                output.push(tokio::spawn(redact_output(
+                    stderr,
+                    tokio::io::stderr(),
+                    redactor,
+                )));
+            }
+        }
+        Ok(Running::new(child, output))
+    }
+}
+
+/// Start the command and wait for it, forwarding signals and, when watching,
+/// restarting it as the files change
+async fn supervise(
+    launch: &Launch<'_>,
+    env: LoadedEnv,
+    grace_period: Option<Duration>,
+    watch: Option<(FileWatcher, Reload<'_>)>,
+) -> Result<ExitStatus> {
+    let (mut watcher, reload) = watch.unzip();
+
+    // Listen before spawning, so no signal can reach us unhandled while the command runs
+    let mut signals = Signals::new()?;
+    let mut running = Some(launch.spawn(&env)?);
+
+    // The environment to start the command with once the current one has stopped
+    let mut restart: Option<LoadedEnv> = None;
+    let mut last_status = ExitStatus::default();
+    let mut shutting_down = false;
+    let mut kill_at: Option<Instant> = None;
 
-    let mut variables = parser.variables().clone();
+    loop {
+        let kill_timer = async move {
+            match kill_at {
+                Some(at) => sleep_until(at).await,
+                None => pending().await,
+            }
+        };
+        let command_pid = running.as_ref().and_then(Running::pid);
+        let watching = !shutting_down && restart.is_none();
 
-    // Find private key for decryption
-    let private_key = find_private_key(env_file, keys_file);
+        tokio::select! {
+            status = wait(&mut running) => {
+                let status = status?;
+                running = None;
+                kill_at = None;
 
-    // Decrypt encrypted values
-    if let Ok(private_key) = private_key {
-        for (key, value) in variables.iter_mut() {
-            if value.starts_with("encrypted:") {
-                match decrypt(value, &private_key) {
-                    Ok(decrypted) => {
-                        *value = decrypted;
-                        debug!("Decrypted key: {}", key);
+                if let Some(env) = restart.take() {
+                    info!("Restarting command");
+                    running = Some(launch.spawn(&env)?);
+                    continue;
+                }
+                if watcher.is_none() || shutting_down || interrupted(status) {
+                    return Ok(status);
+                }
+                info!("Command exited with code {}, waiting for changes", exit_code(status));
+                last_status = status;
+            }
+            signal = signals.recv(command_pid) => {
+                let Some(command) = &running else {
+                    // Between runs there is nothing to stop
+                    if is_terminating(signal) {
+                        return Ok(last_status);
                     }
-                    Err(e) => {
-                        debug!("Failed to decrypt {}: {:?}", key, e);
-                        // Continue with encrypted value
+                    continue;
+                };
+
+                debug!("Forwarding {:?} to the command", signal);
+                command.forward(signal);
+                if is_terminating(signal) {
+                    shutting_down = true;
+                    restart = None;
+                    if kill_at.is_none() {
+                        kill_at = grace_period.map(|grace| Instant::now() + grace);
                     }
                 }
             }
+            _ = kill_timer => {
+                warn!("Command did not exit within the grace period, killing it");
+                if let Some(command) = &mut running {
+                    command.kill();
+                }
+                kill_at = None;
+            }
+            _ = changed(&mut watcher), if watching => {
+                let reload = reload.expect("a watcher comes with a reload");
+                match reload() {
+                    Err(e) => error!("Keeping the current command, reloading failed: {}", e),
+                    Ok(env) => match &mut running {
+                        Some(command) => {
+                            info!("Files changed, stopping the command");
+                            command.terminate();
+                            kill_at = grace_period.map(|grace| Instant::now() + grace);
+                            restart = Some(env);
+                        }
+                        None => {
+                            info!("Files changed, starting the command");
+                            running = Some(launch.spawn(&env)?);
+                        }
+                    },
+                }
+            }
         }
     }
+}
 
-    // Remove DOTENV_PUBLIC_KEY from exported variables
-    variables.remove("DOTENV_PUBLIC_KEY");
+async fn wait(running: &mut Option<Running>) -> std::io::Result<ExitStatus> {
+    match running {
+        Some(command) => command.wait().await,
+        None => pending().await,
+    }
+}
 
-    Ok(variables)
+async fn changed(watcher: &mut Option<FileWatcher>) {
+    match watcher {
+        Some(watcher) => watcher.changed().await,
+        None => pending().await,
+    }
 }
 
-fn find_private_key(env_file: &Path, keys_file: Option<&Path>) -> Result<String> {
-    if let Some(keys_path) = keys_file {
-        if keys_path.exists() {
-            let content = read_file(keys_path)?;
-            if let Some(key) = extract_key_from_content(&content, "DOTENV_PRIVATE_KEY") {
-                return Ok(key);
-            }
+/// Copy a stream of the command's output to ours, redacting secrets
+///
+/// Each chunk is written as soon as it is read, apart from a possible start
+/// of a secret, so line-by-line output stays line by line.
+async fn redact_output<R, W>(mut reader: R, mut writer: W, redactor: Arc<Redactor>)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut stream = redactor.stream();
+    let mut buffer = vec![0; 8192];
+    loop {
+        let (output, done) = match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => (stream.finish(), true),
+            Ok(read) => (stream.push(&buffer[..read]), false),
+        };
+        if let Err(e) = async {
+            writer.write_all(&output).await?;
+            writer.flush().await
+        }
+        .await
+        {
+            debug!("Stopped forwarding output: {}", e);
+            return;
+        }
+        if done {
+            return;
         }
     }
+}
 
-    if let Some(parent) = env_file.parent() {
-        let default_keys = parent.join(".env.keys");
-        if default_keys.exists() {
-            let content = read_file(&default_keys)?;
-            if let Some(key) = extract_key_from_content(&content, "DOTENV_PRIVATE_KEY") {
-                return Ok(key);
-            }
+/// Whether the command was stopped with Ctrl-C, which ends a watch
+fn interrupted(status: ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal() == Some(nix::sys::signal::Signal::SIGINT as i32)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        false
+    }
+}
+
+/// A started command and, on Unix, its process group
+struct Running {
+    child: Child,
+    // Tasks copying redacted output, which end once the command's pipes close
+    output: Vec<JoinHandle<()>>,
+    #[cfg(unix)]
+    pgid: Option<nix::unistd::Pid>,
+    #[cfg(unix)]
+    _foreground: Foreground,
+}
+
+impl Running {
+    /// Wait for the command to exit and for its redacted output to be written
+    async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child.wait().await?;
+        // Only dropped once finished, so waiting again after a cancellation is safe
+        while let Some(output) = self.output.last_mut() {
+            let _ = output.await;
+            self.output.pop();
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(unix)]
+impl Running {
+    fn new(child: Child, output: Vec<JoinHandle<()>>) -> Self {
+        let pgid = child.id().map(|id| nix::unistd::Pid::from_raw(id as i32));
+        Self {
+            child,
+            output,
+            pgid,
+            _foreground: Foreground::hand_to(pgid),
         }
     }
 
-    if let Ok(key) = std::env::var("DOTENV_PRIVATE_KEY") {
-        return Ok(key);
+    fn pid(&self) -> Option<nix::unistd::Pid> {
+        self.pgid
+    }
+
+    fn forward(&self, signal: ForwardedSignal) {
+        if let Some(pgid) = self.pgid {
+            let _ = nix::sys::signal::killpg(pgid, signal);
+        }
+    }
+
+    /// Ask the command to exit
+    fn terminate(&mut self) {
+        self.forward(ForwardedSignal::SIGTERM);
+    }
+
+    fn kill(&mut self) {
+        self.forward(ForwardedSignal::SIGKILL);
+        let _ = self.child.start_kill();
+    }
+}
+
+#[cfg(not(unix))]
+impl Running {
+    fn new(child: Child, output: Vec<JoinHandle<()>>) -> Self {
+        Self { child, output }
     }
 
-    Err(DotenvxError::MissingPrivateKey {
-        key_name: "DOTENV_PRIVATE_KEY".to_string(),
-    })
+    fn pid(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    fn forward(&self, signal: ForwardedSignal) {
+        match signal {}
+    }
+
+    /// Ask the command to exit, which without signals means killing it
+    fn terminate(&mut self) {
+        self.kill();
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.start_kill();
+    }
+}
+
+#[cfg(unix)]
+type ForwardedSignal = nix::sys::signal::Signal;
+
+/// No signals are forwarded outside Unix
+#[cfg(not(unix))]
+#[derive(Debug, Clone, Copy)]
+enum ForwardedSignal {}
+
+#[cfg(unix)]
+fn is_terminating(signal: ForwardedSignal) -> bool {
+    !matches!(signal, ForwardedSignal::SIGUSR1 | ForwardedSignal::SIGUSR2)
+}
+
+#[cfg(not(unix))]
+fn is_terminating(signal: ForwardedSignal) -> bool {
+    match signal {}
+}
+
+/// The signals received by this process that are meant for the command
+#[cfg(unix)]
+struct Signals {
+    forwarded: Vec<(tokio::signal::unix::Signal, ForwardedSignal)>,
+    child: tokio::signal::unix::Signal,
+    // As PID 1 in a container, orphaned processes are re-parented to us
+    init: bool,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let forwarded = [
+            (SignalKind::interrupt(), ForwardedSignal::SIGINT),
+            (SignalKind::terminate(), ForwardedSignal::SIGTERM),
+            (SignalKind::hangup(), ForwardedSignal::SIGHUP),
+            (SignalKind::quit(), ForwardedSignal::SIGQUIT),
+            (SignalKind::user_defined1(), ForwardedSignal::SIGUSR1),
+            (SignalKind::user_defined2(), ForwardedSignal::SIGUSR2),
+        ]
+        .into_iter()
+        .map(|(kind, forwarded)| Ok((signal(kind)?, forwarded)))
+        .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            forwarded,
+            child: signal(SignalKind::child())?,
+            init: is_init(),
+        })
+    }
+
+    /// Wait for the next signal to forward, reaping orphans other than the
+    /// command in the meantime
+    async fn recv(&mut self, command: Option<nix::unistd::Pid>) -> ForwardedSignal {
+        use std::task::Poll;
+
+        std::future::poll_fn(|cx| {
+            if self.init {
+                while self.child.poll_recv(cx).is_ready() {
+                    reap_orphans(command);
+                }
+            }
+            for (stream, signal) in &mut self.forwarded {
+                if stream.poll_recv(cx).is_ready() {
+                    return Poll::Ready(*signal);
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self, _command: Option<u32>) -> ForwardedSignal {
+        pending().await
+    }
 }
 
-fn extract_key_from_content(content: &str, key_name: &str) -> Option<String> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with(&format!("{}=", key_name)) {
-            let value = &trimmed[key_name.len() + 1..];
-            return Some(parse_value(value));
+/// Reap exited orphans, leaving the command itself to be waited on by tokio
+#[cfg(target_os = "linux")]
+fn reap_orphans(command: Option<nix::unistd::Pid>) {
+    use nix::sys::wait::{waitid, waitpid, Id, WaitPidFlag};
+
+    // WNOWAIT peeks, so the command's own status is never consumed here
+    let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+    while let Ok(status) = waitid(Id::All, flags) {
+        match status.pid() {
+            Some(pid) if Some(pid) != command => {
+                let _ = waitpid(pid, None);
+            }
+            _ => break,
         }
     }
-    None
 }
 
-fn parse_value(value: &str) -> String {
-    let value = value.trim();
-    if ((value.starts_with('"') && value.ends_with('"'))
-        || (value.starts_with('\'') && value.ends_with('\'')))
-        && value.len() >= 2
-    {
-        return value[1..value.len() - 1].to_string();
+#[cfg(all(unix, not(target_os = "linux")))]
+fn reap_orphans(_command: Option<nix::unistd::Pid>) {}
+
+/// Gives the controlling terminal to the command's process group while it
+/// runs, so it can read input and receives Ctrl-C from the terminal directly
+#[cfg(unix)]
+struct Foreground {
+    restore: Option<nix::unistd::Pid>,
+}
+
+#[cfg(unix)]
+impl Foreground {
+    fn hand_to(pgid: Option<nix::unistd::Pid>) -> Self {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::{getpgrp, tcgetpgrp, tcsetpgrp};
+
+        let Some(pgid) = pgid else {
+            return Self { restore: None };
+        };
+        let stdin = std::io::stdin();
+        let restore = match tcgetpgrp(&stdin) {
+            Ok(foreground) if foreground == getpgrp() && tcsetpgrp(&stdin, pgid).is_ok() => {
+                // Resume the command in case it touched the terminal before owning it
+                let _ = killpg(pgid, Signal::SIGCONT);
+                Some(foreground)
+            }
+            _ => None,
+        };
+        Self { restore }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        use nix::sys::signal::{pthread_sigmask, SigSet, SigmaskHow, Signal};
+        use nix::unistd::tcsetpgrp;
+
+        let Some(pgrp) = self.restore else {
+            return;
+        };
+
+        // A background process may only take the terminal back while blocking SIGTTOU
+        let mut blocked = SigSet::empty();
+        blocked.add(Signal::SIGTTOU);
+        let mut previous = SigSet::empty();
+        if pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&blocked), Some(&mut previous)).is_ok() {
+            let _ = tcsetpgrp(std::io::stdin(), pgrp);
+            let _ = pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&previous), None);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["-c".to_string(), script.to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_exit_codes() {
+        let loader = EnvLoader::new();
+        let options = RunOptions::default();
+
+        let code = run_command(&[], &loader, "sh", &sh("exit 3"), &options)
+            .await
+            .unwrap();
+        assert_eq!(code, 3);
+
+        let code = run_command(&[], &loader, "sh", &sh("kill -TERM $$"), &options)
+            .await
+            .unwrap();
+        assert_eq!(code, 128 + 15);
+    }
+
+    #[tokio::test]
+    async fn test_runs_in_own_process_group() {
+        let loader = EnvLoader::new();
+        let script = format!(
+            "[ \"$(ps -o pgid= -p $$ | tr -d ' ')\" = \"$$\" ] && [ $$ != {} ]",
+            std::process::id()
+        );
+        let code = run_command(&[], &loader, "sh", &sh(&script), &RunOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(code, 0);
+    }
+
+    #[tokio::test]
+    async fn test_watch_restarts() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let local_file = temp.path().join(".env.local");
+        let out = temp.path().join("out");
+        std::fs::write(&env_file, "A=1\n").unwrap();
+
+        // Interrupting itself on the last value ends the watch
+        let script = format!(
+            "echo $A >> {}; [ $A = 3 ] && kill -INT $$; exec sleep 10",
+            out.display()
+        );
+        let options = RunOptions {
+            watch: true,
+            ..RunOptions::default()
+        };
+        let files = [env_file.clone(), local_file.clone()];
+        let run = tokio::spawn(async move {
+            let loader = EnvLoader::new();
+            let files: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+            run_command(&files, &loader, "sh", &sh(&script), &options).await
+        });
+
+        let wait_for = |expected: &'static str| {
+            let out = out.clone();
+            async move {
+                for _ in 0..100 {
+                    if std::fs::read_to_string(&out).unwrap_or_default() == expected {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                panic!(
+                    "expected {:?} in {:?}",
+                    expected,
+                    std::fs::read_to_string(&out)
+                );
+            }
+        };
+        wait_for("1\n").await;
+
+        // A file that fails to load keeps the current command running
+        std::fs::write(&env_file, "not an assignment\n").unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!run.is_finished());
+
+        std::fs::write(&env_file, "A=2\n").unwrap();
+        wait_for("1\n2\n").await;
+
+        // So does a file that did not exist when the command started
+        std::fs::write(&local_file, "A=3\n").unwrap();
+        wait_for("1\n2\n3\n").await;
+        assert_eq!(run.await.unwrap().unwrap(), 128 + 2);
+    }
+
+    fn process_env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_inherited_env() {
+        let process_env = process_env(&[
+            ("DOTENV_PRIVATE_KEY_RUN_TEST", "key"),
+            ("RUN_TEST_INHERITED", "1"),
+            ("PATH", "/usr/bin"),
+            ("HOME_DIR_OF_TEST", "/home/test"),
+        ]);
+
+        let options = RunOptions {
+            process_env: process_env.clone(),
+            ..RunOptions::default()
+        };
+        let env = options.inherited_env();
+        assert!(!env.contains_key("DOTENV_PRIVATE_KEY_RUN_TEST"));
+        assert!(env.contains_key("RUN_TEST_INHERITED"));
+        assert!(env.contains_key("HOME_DIR_OF_TEST"));
+
+        let options = RunOptions {
+            clean: true,
+            pass: vec!["RUN_TEST_*".to_string()],
+            process_env: process_env.clone(),
+            ..RunOptions::default()
+        };
+        let mut keys: Vec<String> = options.inherited_env().into_keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["PATH", "RUN_TEST_INHERITED"]);
+
+        let options = RunOptions {
+            pass: vec!["DOTENV_PRIVATE_KEY_RUN_TEST".to_string()],
+            process_env,
+            ..RunOptions::default()
+        };
+        assert!(options
+            .inherited_env()
+            .contains_key("DOTENV_PRIVATE_KEY_RUN_TEST"));
+    }
+
+    #[test]
+    fn test_prepare_env_precedence() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        std::fs::write(
+            &env_file,
+            "RUN_TEST_SHADOWED=file\nRUN_TEST_FILE=file\nDOTENVX_RUN_TEST=file\n",
+        )
+        .unwrap();
+        let process_env = process_env(&[("RUN_TEST_SHADOWED", "process")]);
+
+        let loader = EnvLoader::new().process_env(process_env.clone());
+        let options = RunOptions {
+            process_env: process_env.clone(),
+            ..RunOptions::default()
+        };
+        let env = prepare_env(&[env_file.as_path()], &loader, &options).unwrap();
+        assert_eq!(env.variables["RUN_TEST_SHADOWED"], "process");
+        assert_eq!(env.variables["RUN_TEST_FILE"], "file");
+        // Control variables are scrubbed even when a file sets them
+        assert!(!env.variables.contains_key("DOTENVX_RUN_TEST"));
+
+        let options = RunOptions {
+            overload: true,
+            process_env,
+            ..RunOptions::default()
+        };
+        let env = prepare_env(&[env_file.as_path()], &loader, &options).unwrap();
+        assert_eq!(env.variables["RUN_TEST_SHADOWED"], "file");
+    }
+
+    #[test]
+    fn test_use_exec() {
+        let mut options = RunOptions::default();
+        assert!(!options.use_exec().unwrap());
+
+        options.exec = Some(true);
+        assert!(options.use_exec().unwrap());
+
+        options.exec = None;
+        assert_eq!(options.use_exec().unwrap(), !is_init());
+
+        // The files must be removed after the command exits
+        options.as_files = vec!["DB_PASSWORD".to_string()];
+        assert!(!options.use_exec().unwrap());
+        options.exec = Some(true);
+        assert!(options.use_exec().is_err());
     }
-    value.to_string()
 }
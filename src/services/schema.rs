@@ -0,0 +1,426 @@
+use crate::parser::document::{Document, Line};
+use crate::parser::Violation;
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::read_file;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Files searched for a schema, in order of preference
+pub const SCHEMA_FILES: &[&str] = &[".env.schema", ".env.example"];
+
+/// Expected environment variables, read from annotated .env-style files
+///
+/// Comment lines directly above an assignment annotate its key:
+///
+/// ```text
+/// # @required @type=url
+/// DATABASE_URL=
+/// # @type=enum(debug,info,warn)
+/// LOG_LEVEL=info
+/// # @required @sensitive
+/// API_TOKEN=
+/// ```
+///
+/// Values in the schema are only examples and are never checked or loaded.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+/// A key declared in a [`Schema`]
+#[derive(Debug, Clone, Default)]
+pub struct Field {
+    /// Variable name
+    pub key: String,
+    /// Whether the variable must be set to a non-empty value
+    pub required: bool,
+    /// Type the value must have, if set
+    pub value_type: Option<ValueType>,
+    /// Whether the value is secret and must be stored encrypted
+    pub sensitive: bool,
+}
+
+/// Value types understood by `@type=`
+#[derive(Debug, Clone)]
+pub enum ValueType {
+    /// `url`: a scheme followed by `://` and a non-empty remainder
+    Url,
+    /// `int`: a signed 64-bit integer
+    Int,
+    /// `bool`: `true`, `false`, `1`, `0`, `yes` or `no`, in any case
+    Bool,
+    /// `enum(a,b)`: one of the listed values
+    Enum(Vec<String>),
+    /// `regex(...)`: a value the whole pattern matches
+    Regex(Regex),
+}
+
+impl ValueType {
+    fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let argument = |name: &str| {
+            spec.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('('))
+                .and_then(|rest| rest.strip_suffix(')'))
+        };
+
+        match spec {
+            "url" => Ok(Self::Url),
+            "int" => Ok(Self::Int),
+            "bool" => Ok(Self::Bool),
+            _ => {
+                if let Some(values) = argument("enum") {
+                    let values: Vec<String> =
+                        values.split(',').map(|v| v.trim().to_string()).collect();
+                    if values.iter().any(String::is_empty) {
+                        return Err(format!("empty value in '{}'", spec));
+                    }
+                    Ok(Self::Enum(values))
+                } else if let Some(pattern) = argument("regex") {
+                    Regex::new(&format!("^(?:{})$", pattern))
+                        .map(Self::Regex)
+                        .map_err(|e| format!("invalid pattern in '{}': {}", spec, e))
+                } else {
+                    Err(format!(
+                        "unknown type '{}' (expected url, int, bool, enum(...) or regex(...))",
+                        spec
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Why `value` does not have this type, if it does not
+    fn check(&self, value: &str) -> Option<String> {
+        let valid = match self {
+            Self::Url => value.split_once("://").is_some_and(|(scheme, rest)| {
+                !rest.is_empty()
+                    && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            }),
+            Self::Int => value.parse::<i64>().is_ok(),
+            Self::Bool => matches!(
+                value.to_ascii_lowercase().as_str(),
+                "true" | "false" | "1" | "0" | "yes" | "no"
+            ),
+            Self::Enum(values) => values.iter().any(|v| v == value),
+            Self::Regex(regex) => regex.is_match(value),
+        };
+        if valid {
+            return None;
+        }
+
+        Some(match self {
+            Self::Url => "must be a URL".to_string(),
+            Self::Int => "must be an integer".to_string(),
+            Self::Bool => "must be a boolean (true, false, 1, 0, yes or no)".to_string(),
+            Self::Enum(values) => format!("must be one of: {}", values.join(", ")),
+            Self::Regex(regex) => {
+                // Without the anchors added by `parse`
+                let pattern = regex.as_str();
+                format!("must match {}", &pattern[4..pattern.len() - 2])
+            }
+        })
+    }
+}
+
+impl Schema {
+    /// Parse a schema from annotated .env content
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the schema file
+    ///
+    /// # Returns
+    ///
+    /// The schema, or a parse error for an unknown annotation or type
+    pub fn parse(content: &str) -> Result<Self> {
+        let document = Document::parse(content);
+        let mut fields = Vec::new();
+        let mut pending = Field::default();
+
+        for (i, line) in document.lines.iter().enumerate() {
+            match line {
+                Line::Comment(comment) => {
+                    let text = comment.trim_start().trim_start_matches('#').trim();
+                    // Comments that do not start with an annotation are documentation
+                    if text.starts_with('@') {
+                        annotate(&mut pending, text).map_err(|message| {
+                            DotenvxError::ParseError {
+                                line: i + 1,
+                                message,
+                            }
+                        })?;
+                    }
+                }
+                Line::Entry(entry) => {
+                    let mut field = std::mem::take(&mut pending);
+                    field.key = entry.key.clone();
+                    fields.retain(|f: &Field| f.key != field.key);
+                    fields.push(field);
+                }
+                // Annotations only apply to the assignment right below them
+                Line::Blank(_) | Line::Invalid(_) => pending = Field::default(),
+            }
+        }
+
+        Ok(Self { fields })
+    }
+
+    /// Read and parse a schema file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&read_file(path)?).map_err(|e| match e {
+            DotenvxError::ParseError { line, message } => DotenvxError::ParseError {
+                line,
+                message: format!("{}: {}", path.display(), message),
+            },
+            e => e,
+        })
+    }
+
+    /// Find the schema for a set of .env files
+    ///
+    /// # Arguments
+    ///
+    /// * `explicit` - Schema file given by the user, which must exist
+    /// * `env_file` - An .env file whose directory is searched for [`SCHEMA_FILES`]
+    ///
+    /// # Returns
+    ///
+    /// The schema, or `None` if no explicit file was given and none was found
+    pub fn discover(explicit: Option<&Path>, env_file: Option<&Path>) -> Result<Option<Self>> {
+        if let Some(path) = explicit {
+            return Self::load(path).map(Some);
+        }
+        let dir = env_file.and_then(Path::parent).unwrap_or(Path::new(""));
+        match find_schema(dir) {
+            Some(path) => Self::load(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Like [`Schema::discover`], failing when there is no schema
+    pub fn require(explicit: Option<&Path>, env_file: Option<&Path>) -> Result<Self> {
+        Self::discover(explicit, env_file)?.ok_or_else(|| {
+            DotenvxError::Other(format!(
+                "no schema found: create {} or pass --schema",
+                SCHEMA_FILES.join(" or ")
+            ))
+        })
+    }
+
+    /// The declared keys, in file order
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Keys annotated `@sensitive`
+    pub fn sensitive_keys(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter(|f| f.sensitive)
+            .map(|f| f.key.clone())
+            .collect()
+    }
+
+    /// Check an environment against the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The variables to check
+    /// * `unevaluated` - Keys holding the text of a command that was not run,
+    ///   which are only checked for being set
+    ///
+    /// # Returns
+    ///
+    /// Every violation, in schema order
+    pub fn validate(
+        &self,
+        env: &HashMap<String, String>,
+        unevaluated: &HashSet<String>,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for field in &self.fields {
+            let message = match env.get(&field.key).filter(|v| !v.is_empty()) {
+                None if field.required => Some("is required but not set".to_string()),
+                None => None,
+                Some(_) if unevaluated.contains(&field.key) => None,
+                Some(value) => field.value_type.as_ref().and_then(|t| t.check(value)),
+            };
+            if let Some(message) = message {
+                violations.push(Violation {
+                    key: field.key.clone(),
+                    message,
+                });
+            }
+        }
+        violations
+    }
+
+    /// Check an environment against the schema, failing with every violation
+    pub fn check(
+        &self,
+        env: &HashMap<String, String>,
+        unevaluated: &HashSet<String>,
+    ) -> Result<()> {
+        let violations = self.validate(env, unevaluated);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(DotenvxError::ValidationFailed { violations })
+    }
+}
+
+/// The first of [`SCHEMA_FILES`] that exists in `dir`
+pub fn find_schema(dir: &Path) -> Option<PathBuf> {
+    SCHEMA_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// Apply the annotations in one comment, such as `@required @type=int`
+fn annotate(field: &mut Field, text: &str) -> std::result::Result<(), String> {
+    for annotation in split_annotations(text) {
+        let Some(annotation) = annotation.strip_prefix('@') else {
+            return Err(format!("expected an annotation, found '{}'", annotation));
+        };
+        match annotation.split_once('=') {
+            None if annotation == "required" => field.required = true,
+            None if annotation == "sensitive" => field.sensitive = true,
+            Some(("type", spec)) => field.value_type = Some(ValueType::parse(spec)?),
+            _ => {
+                return Err(format!(
+                    "unknown annotation '@{}' (expected @required, @type=... or @sensitive)",
+                    annotation
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split on whitespace outside parentheses, so `regex(a b)` stays whole
+fn split_annotations(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    parts.push(&text[s..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        parts.push(&text[s..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "\
+# Connection string for the primary database
+# @required @type=url
+DATABASE_URL=postgres://localhost/app
+
+# @type=int
+PORT=3000
+# @type=enum(debug, info, warn)
+LOG_LEVEL=info
+# @type=regex([a-z]+ [0-9]+)
+RELEASE=
+
+# @required
+# @sensitive
+API_TOKEN=
+# @type=bool
+
+DEBUG=
+";
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_annotations() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let keys: Vec<&str> = schema.fields().iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "DATABASE_URL",
+                "PORT",
+                "LOG_LEVEL",
+                "RELEASE",
+                "API_TOKEN",
+                "DEBUG"
+            ]
+        );
+        assert!(schema.fields()[0].required);
+        assert!(!schema.fields()[1].required);
+        // A blank line detaches annotations from the next key
+        assert!(schema.fields()[5].value_type.is_none());
+        assert_eq!(schema.sensitive_keys(), ["API_TOKEN"]);
+
+        assert!(Schema::parse("# @requird\nA=\n").is_err());
+        assert!(Schema::parse("# @type=float\nA=\n").is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let violations = schema.validate(
+            &env(&[
+                ("DATABASE_URL", "localhost"),
+                ("PORT", "eighty"),
+                ("LOG_LEVEL", "trace"),
+                ("RELEASE", "v1 2"),
+                ("API_TOKEN", ""),
+            ]),
+            &HashSet::new(),
+        );
+        let rendered: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            rendered,
+            [
+                "DATABASE_URL: must be a URL",
+                "PORT: must be an integer",
+                "LOG_LEVEL: must be one of: debug, info, warn",
+                "RELEASE: must match [a-z]+ [0-9]+",
+                "API_TOKEN: is required but not set",
+            ]
+        );
+
+        let valid = env(&[
+            ("DATABASE_URL", "postgres://db/app"),
+            ("PORT", "-1"),
+            ("RELEASE", "v 2"),
+            ("API_TOKEN", "secret"),
+        ]);
+        assert!(schema.check(&valid, &HashSet::new()).is_ok());
+
+        // A command that was not run can only be checked for being set
+        let mut unrun = valid.clone();
+        unrun.insert("PORT".to_string(), "$(cat port)".to_string());
+        assert!(schema.check(&unrun, &HashSet::new()).is_err());
+        let unevaluated = HashSet::from(["PORT".to_string()]);
+        assert!(schema.check(&unrun, &unevaluated).is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::parser::is_valid_key;
+use crate::utils::error::{DotenvxError, Result};
+use glob::Pattern;
+use std::collections::HashMap;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{debug, warn};
+
+/// Suffix of the variable that holds the path of a value's file
+pub const FILE_SUFFIX: &str = "_FILE";
+
+/// A memory-backed directory preferred for secret files, where it exists
+#[cfg(target_os = "linux")]
+const TMPFS_DIR: Option<&str> = Some("/dev/shm");
+
+/// A memory-backed directory preferred for secret files, where it exists
+#[cfg(not(target_os = "linux"))]
+const TMPFS_DIR: Option<&str> = None;
+
+/// Hands values to a command as files rather than environment variables
+///
+/// Each selected `KEY` is written to a file readable only by the current
+/// user, in a private directory, and replaced by `KEY_FILE` holding the
+/// file's path. This follows the `_FILE` convention of images such as
+/// postgres, and keeps the values out of `/proc/<pid>/environ`. The
+/// directory is removed when this is dropped.
+#[derive(Debug)]
+pub struct SecretFiles {
+    dir: PathBuf,
+    keys: Vec<String>,
+    patterns: Vec<Pattern>,
+}
+
+impl SecretFiles {
+    /// Create the directory for the files, on tmpfs if available
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Names of the variables to write to files
+    /// * `patterns` - Glob patterns selecting more variables
+    pub fn create(keys: &[String], patterns: &[Pattern]) -> Result<Self> {
+        let parent = TMPFS_DIR
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_dir())
+            .unwrap_or_else(std::env::temp_dir);
+        Self::create_in(&parent, keys, patterns)
+    }
+
+    /// Like [`SecretFiles::create`], with the directory inside `parent`
+    pub fn create_in(parent: &Path, keys: &[String], patterns: &[Pattern]) -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+
+        // Creating the directory fails if it exists, so nobody else can own it
+        let dir = loop {
+            let dir = parent.join(format!(
+                "dotenvx-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match builder.create(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        debug!("Writing secret files to {}", dir.display());
+
+        Ok(Self {
+            dir,
+            keys: keys.to_vec(),
+            patterns: patterns.to_vec(),
+        })
+    }
+
+    /// The directory holding the files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether a variable is written to a file
+    pub fn selects(&self, key: &str) -> bool {
+        self.keys.iter().any(|k| k == key) || self.patterns.iter().any(|p| p.matches(key))
+    }
+
+    /// Write the selected variables to files
+    ///
+    /// Files of variables no longer selected are removed, so this can be
+    /// called again before each restart of the command.
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - The command's environment
+    ///
+    /// # Returns
+    ///
+    /// The environment with each selected `KEY` replaced by `KEY_FILE`
+    pub fn write(&self, variables: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+        let mut env = HashMap::with_capacity(variables.len());
+        let mut written = Vec::new();
+        for (key, value) in variables {
+            if !self.selects(key) {
+                env.entry(key.clone()).or_insert_with(|| value.clone());
+                continue;
+            }
+
+            // The key names the file, so it must not be able to leave the directory
+            if !is_valid_key(key) {
+                return Err(DotenvxError::Other(format!(
+                    "cannot write '{}' to a file: not a valid variable name",
+                    key
+                )));
+            }
+            let path = self.dir.join(key);
+            write_private(&path, value)?;
+            written.push(path.clone());
+            env.insert(
+                format!("{}{}", key, FILE_SUFFIX),
+                path.to_string_lossy().into_owned(),
+            );
+        }
+
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !written.contains(&path) {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(env)
+    }
+}
+
+impl Drop for SecretFiles {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            warn!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+/// Write `value` to a file only the current user can read
+fn write_private(path: &Path, value: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_writes_selected_values() {
+        let temp = TempDir::new().unwrap();
+        let files = SecretFiles::create_in(
+            temp.path(),
+            &["DB_PASSWORD".to_string()],
+            &[Pattern::new("*_SECRET").unwrap()],
+        )
+        .unwrap();
+        let dir = files.dir().to_path_buf();
+
+        let written = files
+            .write(&env(&[
+                ("DB_PASSWORD", "hunter2"),
+                ("API_SECRET", "s3cret"),
+                ("PORT", "8080"),
+            ]))
+            .unwrap();
+        let path = dir.join("DB_PASSWORD");
+        assert_eq!(
+            written,
+            env(&[
+                ("DB_PASSWORD_FILE", path.to_str().unwrap()),
+                ("API_SECRET_FILE", dir.join("API_SECRET").to_str().unwrap()),
+                ("PORT", "8080"),
+            ])
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hunter2");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&dir), 0o700);
+            assert_eq!(mode(&path), 0o600);
+        }
+
+        // Rewriting drops files that are no longer needed
+        files.write(&env(&[("DB_PASSWORD", "hunter3")])).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hunter3");
+        assert!(!dir.join("API_SECRET").exists());
+
+        drop(files);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_rejects_keys_that_are_paths() {
+        let temp = TempDir::new().unwrap();
+        let outside = temp.path().join("x");
+        let files =
+            SecretFiles::create_in(temp.path(), &[], &[Pattern::new("*x").unwrap()]).unwrap();
+
+        for key in ["../x", outside.to_str().unwrap(), "/tmp/x"] {
+            assert!(files.write(&env(&[(key, "secret")])).is_err(), "{}", key);
+        }
+        assert!(!outside.exists());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::crypto::{encrypt, Keypair};
+use crate::parser::{log_warnings, quote_value, DotenvParser, EnvWriter};
+use crate::utils::error::{DotenvxError, Result};
+use crate::utils::fs::{read_file, write_file};
+use std::path::Path;
+
+/// Set variables in a .env file, encrypting them unless `plain` is set
+///
+/// Existing assignments are replaced in place and new keys are appended. The
+/// file's public key is reused; if it has none, a keypair is generated and
+/// the private key is saved to the .env.keys file.
+///
+/// # Arguments
+///
+/// * `env_file` - Path to the .env file, created if missing
+/// * `keys_file` - Optional path to the .env.keys file
+/// * `values` - Keys and plaintext values to set, in order
+/// * `plain` - Store values as plain text
+/// * `literal` - Write plain values so they load unchanged, rather than
+///   expanding the `$VAR` references in them
+/// * `replace` - Drop every existing variable, keeping only the public key
+/// * `strict` - Reject invalid variable names and duplicate keys
+pub fn set_values(
+    env_file: &Path,
+    keys_file: Option<&Path>,
+    values: &[(String, String)],
+    plain: bool,
+    literal: bool,
+    replace: bool,
+    strict: bool,
+) -> Result<()> {
+    // Read existing file or create empty content
+    let mut content = if env_file.exists() {
+        read_file(env_file)?
+    } else {
+        String::new()
+    };
+
+    let mut parser = DotenvParser::with_path(env_file).strict(strict);
+    if !content.is_empty() {
+        parser.parse(&content)?;
+        log_warnings(parser.diagnostics());
+    }
+    let existing_key = parser.variables().get("DOTENV_PUBLIC_KEY").cloned();
+
+    if replace {
+        let mut writer = EnvWriter::new(Vec::new());
+        if let Some(public_key) = &existing_key {
+            writer.public_key(public_key)?;
+        }
+        content = String::from_utf8_lossy(&writer.into_inner()).into_owned();
+    }
+
+    // Get or create keypair for encryption
+    let public_key = if plain {
+        None
+    } else if let Some(existing_key) = existing_key {
+        Some(existing_key)
+    } else {
+        let keypair = Keypair::generate();
+
+        // Save private key
+        save_private_key(env_file, keys_file, &keypair.private_key())?;
+
+        Some(keypair.public_key())
+    };
+
+    let mut lines = Vec::with_capacity(values.len());
+    for (key, value) in values {
+        let final_value = match &public_key {
+            Some(public_key) => encrypt(value, public_key)?,
+            None => value.clone(),
+        };
+        lines.push((
+            key,
+            format!("{}={}", key, quote_value(&final_value, literal)),
+        ));
+    }
+
+    // Build new content
+    let mut output = String::new();
+
+    // Add public key header if encrypting
+    if let Some(ref pub_key) = public_key {
+        if !content.contains("DOTENV_PUBLIC_KEY") {
+            let mut writer = EnvWriter::new(Vec::new());
+            writer.public_key(pub_key)?;
+            output.push_str(&String::from_utf8_lossy(&writer.into_inner()));
+        }
+    }
+
+    // Process existing content
+    let mut found = vec![false; lines.len()];
+    for line in content.lines() {
+        let trimmed = line.trim();
+        let replacement = lines.iter().position(|(key, _)| {
+            trimmed.starts_with(&format!("{}=", key))
+                || trimmed.starts_with(&format!("export {}=", key))
+        });
+
+        match replacement {
+            Some(index) => {
+                // Replace this line
+                output.push_str(&lines[index].1);
+                output.push('\n');
+                found[index] = true;
+            }
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+
+    // Add keys that were not found
+    for ((_, line), found) in lines.iter().zip(found) {
+        if !found {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    write_file(env_file, &output)
+}
+
+fn save_private_key(env_file: &Path, keys_file: Option<&Path>, private_key: &str) -> Result<()> {
+    let keys_path = if let Some(path) = keys_file {
+        path.to_path_buf()
+    } else {
+        env_file
+            .parent()
+            .ok_or_else(|| DotenvxError::Other("Invalid env file path".to_string()))?
+            .join(".env.keys")
+    };
+
+    let mut content = if keys_path.exists() {
+        read_file(&keys_path)?
+    } else {
+        let mut c = String::new();
+        c.push_str("#/------------------!DOTENV_PRIVATE_KEYS!-------------------/\n");
+        c.push_str("#/ private decryption keys. DO NOT commit to source control /\n");
+        c.push_str("#/     [how it works](https://dotenvx.com/encryption)       /\n");
+        c.push_str("#/----------------------------------------------------------/\n\n");
+        c
+    };
+
+    if !content.contains("DOTENV_PRIVATE_KEY=") {
+        content.push_str(&format!("DOTENV_PRIVATE_KEY={}\n", private_key));
+        write_file(&keys_path, &content)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn load(env_file: &Path) -> std::collections::HashMap<String, String> {
+        let mut parser = DotenvParser::new();
+        parser.parse(&read_file(env_file).unwrap()).unwrap();
+        parser.variables().clone()
+    }
+
+    #[test]
+    fn test_set_plain_merges() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "# keep\nA=1\nB=2\n").unwrap();
+
+        let values = pairs(&[("B", "say \"hi\""), ("C", "$HOME")]);
+        set_values(&env_file, None, &values, true, true, false, false).unwrap();
+
+        assert_eq!(
+            read_file(&env_file).unwrap(),
+            "# keep\nA=1\nB=\"say \\\"hi\\\"\"\nC='$HOME'\n"
+        );
+
+        // Without `literal`, references are left to expand when loaded
+        set_values(
+            &env_file,
+            None,
+            &pairs(&[("C", "$HOME")]),
+            true,
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(read_file(&env_file).unwrap().ends_with("C=\"$HOME\"\n"));
+    }
+
+    #[test]
+    fn test_set_encrypted_replace() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        write_file(&env_file, "OLD=1\n").unwrap();
+
+        set_values(
+            &env_file,
+            None,
+            &pairs(&[("A", "1")]),
+            false,
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+        let public_key = load(&env_file)["DOTENV_PUBLIC_KEY"].clone();
+        assert!(temp.path().join(".env.keys").exists());
+
+        set_values(
+            &env_file,
+            None,
+            &pairs(&[("B", "2")]),
+            false,
+            false,
+            true,
+            false,
+        )
+        .unwrap();
+        let vars = load(&env_file);
+        assert_eq!(vars["DOTENV_PUBLIC_KEY"], public_key);
+        assert!(vars["B"].starts_with("encrypted:"));
+        assert!(!vars.contains_key("A"));
+        assert!(!vars.contains_key("OLD"));
+    }
+}
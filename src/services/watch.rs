@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+
+/// How often watched files are checked, by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long files must stay unchanged before a change is reported, by default
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Detects changes to a set of files by polling their modification time and size
+///
+/// Files that do not exist yet are watched too, so creating one counts as a change.
+#[derive(Debug)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<Stamp>)>,
+    interval: Duration,
+    debounce: Duration,
+    // A change was seen but not reported yet
+    pending: bool,
+}
+
+type Stamp = (SystemTime, u64);
+
+impl FileWatcher {
+    /// Watch the given files with the default interval and debounce
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self::with_timing(files, DEFAULT_POLL_INTERVAL, DEFAULT_DEBOUNCE)
+    }
+
+    /// Watch the given files, polling every `interval` and reporting a change
+    /// once the files have been stable for `debounce`
+    pub fn with_timing(files: Vec<PathBuf>, interval: Duration, debounce: Duration) -> Self {
+        let files = files
+            .into_iter()
+            .map(|path| {
+                let stamp = stamp(&path);
+                (path, stamp)
+            })
+            .collect();
+        Self {
+            files,
+            interval,
+            debounce,
+            pending: false,
+        }
+    }
+
+    /// Wait until a file changes and then stays unchanged for the debounce period
+    ///
+    /// Editors often save in several writes, and this reports them as one change.
+    /// It is cancel safe: a change seen before the future is dropped is
+    /// reported by the next call.
+    pub async fn changed(&mut self) {
+        while !self.pending {
+            self.pending = self.poll();
+            if !self.pending {
+                sleep(self.interval).await;
+            }
+        }
+        loop {
+            sleep(self.debounce).await;
+            if !self.poll() {
+                self.pending = false;
+                return;
+            }
+        }
+    }
+
+    /// Record the current state of every file, returning whether any changed
+    fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, previous) in &mut self.files {
+            let current = stamp(path);
+            if current != *previous {
+                *previous = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use tokio::time::timeout;
+
+    fn watcher(files: Vec<PathBuf>) -> FileWatcher {
+        FileWatcher::with_timing(files, Duration::from_millis(10), Duration::from_millis(50))
+    }
+
+    #[tokio::test]
+    async fn test_reports_changes() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        let keys_file = temp.path().join(".env.keys");
+        fs::write(&env_file, "A=1\n").unwrap();
+
+        let mut watcher = watcher(vec![env_file.clone(), keys_file.clone()]);
+        let wait = Duration::from_millis(200);
+        assert!(timeout(wait, watcher.changed()).await.is_err());
+
+        fs::write(&env_file, "A=12\n").unwrap();
+        assert!(timeout(wait, watcher.changed()).await.is_ok());
+        assert!(timeout(wait, watcher.changed()).await.is_err());
+
+        // Creating a file that did not exist is a change too
+        fs::write(&keys_file, "DOTENV_PRIVATE_KEY=abc\n").unwrap();
+        assert!(timeout(wait, watcher.changed()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_debounces_bursts() {
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join(".env");
+        fs::write(&env_file, "").unwrap();
+
+        let mut watcher = watcher(vec![env_file.clone()]);
+        let writer = tokio::spawn(async move {
+            for i in 1..=5 {
+                fs::write(&env_file, "A=1\n".repeat(i)).unwrap();
+                sleep(Duration::from_millis(20)).await;
+            }
+        });
+
+        timeout(Duration::from_secs(2), watcher.changed())
+            .await
+            .unwrap();
+        // The burst was reported once, after the last write
+        assert!(writer.is_finished());
+        assert!(!watcher.poll());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::parser::diagnostic::{render_diagnostics, render_violations, Diagnostic, Violation};
 use thiserror::Error;
 
 /// Result type alias for dotenvx operations
@@ -36,6 +37,9 @@ pub enum DotenvxError {
     #[error("invalid .env file format at line {line}: {message}")]
     ParseError { line: usize, message: String },
 
+    #[error("{}", render_diagnostics(.diagnostics))]
+    InvalidEnvFile { diagnostics: Vec<Diagnostic> },
+
     #[error("command execution failed: {0}")]
     CommandFailed(String),
 
@@ -63,6 +67,27 @@ pub enum DotenvxError {
     #[error("command substitution error: {0}")]
     CommandSubstitution(String),
 
+    #[error("invalid value for {key}: {message}")]
+    InvalidValue { key: String, message: String },
+
+    #[error("missing field `{0}`")]
+    MissingField(String),
+
+    #[error("deserialization error: {0}")]
+    Deserialize(String),
+
+    #[error("serialization error: {0}")]
+    Serialize(String),
+
+    #[error("{files} file(s) would be reformatted")]
+    Unformatted { files: usize },
+
+    #[error("lint found {errors} error(s)")]
+    LintFailed { errors: usize },
+
+    #[error("{}", render_violations(.violations))]
+    ValidationFailed { violations: Vec<Violation> },
+
     #[error("{0}")]
     Other(String),
 }
@@ -80,6 +105,7 @@ impl DotenvxError {
             Self::InvalidPrivateKey(_) => "INVALID_PRIVATE_KEY",
             Self::EncryptionFailed(_) => "ENCRYPTION_FAILED",
             Self::ParseError { .. } => "PARSE_ERROR",
+            Self::InvalidEnvFile { .. } => "INVALID_ENV_FILE",
             Self::CommandFailed(_) => "COMMAND_FAILED",
             Self::Io(_) => "IO_ERROR",
             Self::Secp256k1(_) => "CRYPTO_ERROR",
@@ -89,6 +115,13 @@ impl DotenvxError {
             Self::Regex(_) => "REGEX_ERROR",
             Self::VariableExpansion(_) => "VARIABLE_EXPANSION_ERROR",
             Self::CommandSubstitution(_) => "COMMAND_SUBSTITUTION_ERROR",
+            Self::InvalidValue { .. } => "INVALID_VALUE",
+            Self::MissingField(_) => "MISSING_FIELD",
+            Self::Deserialize(_) => "DESERIALIZE_ERROR",
+            Self::Serialize(_) => "SERIALIZE_ERROR",
+            Self::Unformatted { .. } => "UNFORMATTED",
+            Self::LintFailed { .. } => "LINT_FAILED",
+            Self::ValidationFailed { .. } => "VALIDATION_FAILED",
             Self::Other(_) => "UNKNOWN_ERROR",
         }
     }